@@ -1,12 +1,30 @@
 #[derive(Default, Clone, Debug)]
 pub struct RepoConfig {
     pub protected_branches: Option<Vec<String>>,
+    pub push_remote: Option<String>,
+    pub pull_remote: Option<String>,
     pub capacity: Option<usize>,
+    pub keep_hours: Option<u64>,
+    pub keep_days: Option<u64>,
+    pub keep_weeks: Option<u64>,
+    pub storage: Option<crate::Storage>,
+    pub keep_alive: Option<bool>,
+    pub include: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
 }
 
 static STACK_FIELD: &str = "stack.stack";
 static PROTECTED_STACK_FIELD: &str = "stack.protected-branch";
+static PUSH_REMOTE_FIELD: &str = "stack.push-remote";
+static PULL_REMOTE_FIELD: &str = "stack.pull-remote";
 static BACKUP_CAPACITY_FIELD: &str = "branch-stash.capacity";
+static KEEP_HOURS_FIELD: &str = "branch-stash.keep-hours";
+static KEEP_DAYS_FIELD: &str = "branch-stash.keep-days";
+static KEEP_WEEKS_FIELD: &str = "branch-stash.keep-weeks";
+static STORAGE_FIELD: &str = "branch-stash.storage";
+static KEEP_ALIVE_FIELD: &str = "branch-stash.keep-alive";
+static INCLUDE_FIELD: &str = "branch-stash.include";
+static EXCLUDE_FIELD: &str = "branch-stash.exclude";
 
 static DEFAULT_PROTECTED_BRANCHES: [&str; 4] = ["main", "master", "dev", "stable"];
 const DEFAULT_CAPACITY: usize = 30;
@@ -96,8 +114,45 @@ impl RepoConfig {
                         .get_or_insert_with(Vec::new)
                         .push(value.into_owned());
                 }
+            } else if key == PUSH_REMOTE_FIELD {
+                config.push_remote = value.map(|v| v.into_owned());
+            } else if key == PULL_REMOTE_FIELD {
+                config.pull_remote = value.map(|v| v.into_owned());
             } else if key == BACKUP_CAPACITY_FIELD {
                 config.capacity = value.as_deref().and_then(|s| s.parse::<usize>().ok());
+            } else if key == KEEP_HOURS_FIELD {
+                config.keep_hours = value
+                    .as_deref()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .and_then(|i| keep_count(KEEP_HOURS_FIELD, i));
+            } else if key == KEEP_DAYS_FIELD {
+                config.keep_days = value
+                    .as_deref()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .and_then(|i| keep_count(KEEP_DAYS_FIELD, i));
+            } else if key == KEEP_WEEKS_FIELD {
+                config.keep_weeks = value
+                    .as_deref()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .and_then(|i| keep_count(KEEP_WEEKS_FIELD, i));
+            } else if key == STORAGE_FIELD {
+                config.storage = value.as_deref().and_then(parse_storage);
+            } else if key == KEEP_ALIVE_FIELD {
+                config.keep_alive = Some(value.as_deref().map(parse_bool).unwrap_or(true));
+            } else if key == INCLUDE_FIELD {
+                if let Some(value) = value {
+                    config
+                        .include
+                        .get_or_insert_with(Vec::new)
+                        .push(value.into_owned());
+                }
+            } else if key == EXCLUDE_FIELD {
+                if let Some(value) = value {
+                    config
+                        .exclude
+                        .get_or_insert_with(Vec::new)
+                        .push(value.into_owned());
+                }
             } else {
                 log::warn!(
                     "Unsupported config: {}={}",
@@ -142,33 +197,55 @@ impl RepoConfig {
     }
 
     pub fn from_gitconfig(config: &git2::Config) -> Self {
-        let protected_branches = config
-            .multivar(PROTECTED_STACK_FIELD, None)
-            .map(|entries| {
-                let mut protected_branches = Vec::new();
-                entries
-                    .for_each(|entry| {
-                        if let Ok(value) = entry.value() {
-                            protected_branches.push(value.to_owned());
-                        }
-                    })
-                    .unwrap();
-                if protected_branches.is_empty() {
-                    None
-                } else {
-                    Some(protected_branches)
-                }
-            })
-            .unwrap_or(None);
+        let protected_branches = get_multivar(config, PROTECTED_STACK_FIELD);
+
+        let push_remote = config.get_string(PUSH_REMOTE_FIELD).ok();
+
+        let pull_remote = config.get_string(PULL_REMOTE_FIELD).ok();
 
         let capacity = config
             .get_i64(BACKUP_CAPACITY_FIELD)
             .map(|i| i as usize)
             .ok();
 
+        let keep_hours = config
+            .get_i64(KEEP_HOURS_FIELD)
+            .ok()
+            .and_then(|i| keep_count(KEEP_HOURS_FIELD, i));
+
+        let keep_days = config
+            .get_i64(KEEP_DAYS_FIELD)
+            .ok()
+            .and_then(|i| keep_count(KEEP_DAYS_FIELD, i));
+
+        let keep_weeks = config
+            .get_i64(KEEP_WEEKS_FIELD)
+            .ok()
+            .and_then(|i| keep_count(KEEP_WEEKS_FIELD, i));
+
+        let storage = config
+            .get_string(STORAGE_FIELD)
+            .ok()
+            .and_then(|s| parse_storage(&s));
+
+        let keep_alive = config.get_bool(KEEP_ALIVE_FIELD).ok();
+
+        let include = get_multivar(config, INCLUDE_FIELD);
+
+        let exclude = get_multivar(config, EXCLUDE_FIELD);
+
         Self {
             protected_branches,
+            push_remote,
+            pull_remote,
             capacity,
+            keep_hours,
+            keep_days,
+            keep_weeks,
+            storage,
+            keep_alive,
+            include,
+            exclude,
         }
     }
 
@@ -181,6 +258,18 @@ impl RepoConfig {
         Ok(())
     }
 
+    pub fn write_workdir(&self, repo: &git2::Repository) -> eyre::Result<()> {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| eyre::eyre!("Cannot write config in bare repository."))?;
+        let config_path = workdir.join(".gitconfig");
+        log::trace!("Loading {}", config_path.display());
+        let mut config = git2::Config::open(&config_path)?;
+        log::info!("Writing {}", config_path.display());
+        self.to_gitconfig(&mut config)?;
+        Ok(())
+    }
+
     pub fn to_gitconfig(&self, config: &mut git2::Config) -> eyre::Result<()> {
         if let Some(protected_branches) = self.protected_branches.as_ref() {
             // Ignore errors if there aren't keys to remove
@@ -189,6 +278,41 @@ impl RepoConfig {
                 config.set_multivar(PROTECTED_STACK_FIELD, "^$", branch)?;
             }
         }
+        if let Some(push_remote) = self.push_remote.as_deref() {
+            config.set_str(PUSH_REMOTE_FIELD, push_remote)?;
+        }
+        if let Some(pull_remote) = self.pull_remote.as_deref() {
+            config.set_str(PULL_REMOTE_FIELD, pull_remote)?;
+        }
+        for (field, patterns) in [
+            (INCLUDE_FIELD, &self.include),
+            (EXCLUDE_FIELD, &self.exclude),
+        ] {
+            if let Some(patterns) = patterns.as_ref() {
+                let _ = config.remove_multivar(field, ".*");
+                for pattern in patterns {
+                    config.set_multivar(field, "^$", pattern)?;
+                }
+            }
+        }
+        if let Some(capacity) = self.capacity {
+            config.set_i64(BACKUP_CAPACITY_FIELD, capacity as i64)?;
+        }
+        for (field, keep) in [
+            (KEEP_HOURS_FIELD, self.keep_hours),
+            (KEEP_DAYS_FIELD, self.keep_days),
+            (KEEP_WEEKS_FIELD, self.keep_weeks),
+        ] {
+            if let Some(keep) = keep {
+                config.set_i64(field, keep as i64)?;
+            }
+        }
+        if let Some(storage) = self.storage {
+            config.set_str(STORAGE_FIELD, &storage.to_string())?;
+        }
+        if let Some(keep_alive) = self.keep_alive {
+            config.set_bool(KEEP_ALIVE_FIELD, keep_alive)?;
+        }
         Ok(())
     }
 
@@ -198,7 +322,24 @@ impl RepoConfig {
             (None, Some(rhs)) => self.protected_branches = Some(rhs),
             (_, _) => (),
         }
+        self.push_remote = other.push_remote.or(self.push_remote);
+        self.pull_remote = other.pull_remote.or(self.pull_remote);
         self.capacity = other.capacity.or(self.capacity);
+        self.keep_hours = other.keep_hours.or(self.keep_hours);
+        self.keep_days = other.keep_days.or(self.keep_days);
+        self.keep_weeks = other.keep_weeks.or(self.keep_weeks);
+        self.storage = other.storage.or(self.storage);
+        self.keep_alive = other.keep_alive.or(self.keep_alive);
+        match (&mut self.include, other.include) {
+            (Some(lhs), Some(rhs)) => lhs.extend(rhs),
+            (None, Some(rhs)) => self.include = Some(rhs),
+            (_, _) => (),
+        }
+        match (&mut self.exclude, other.exclude) {
+            (Some(lhs), Some(rhs)) => lhs.extend(rhs),
+            (None, Some(rhs)) => self.exclude = Some(rhs),
+            (_, _) => (),
+        }
 
         self
     }
@@ -207,10 +348,48 @@ impl RepoConfig {
         self.protected_branches.as_deref().unwrap_or(&[])
     }
 
+    /// Remote to push to, when overriding git's own config
+    pub fn push_remote(&self) -> Option<&str> {
+        self.push_remote.as_deref()
+    }
+
+    /// Remote to pull from, when overriding git's own config
+    pub fn pull_remote(&self) -> Option<&str> {
+        self.pull_remote.as_deref()
+    }
+
     pub fn capacity(&self) -> Option<usize> {
         let capacity = self.capacity.unwrap_or(DEFAULT_CAPACITY);
         (capacity != 0).then_some(capacity)
     }
+
+    /// Time-based retention, when any of it is configured
+    pub fn retention(&self) -> Option<crate::Retention> {
+        if self.keep_hours.is_none() && self.keep_days.is_none() && self.keep_weeks.is_none() {
+            return None;
+        }
+        Some(crate::Retention {
+            hours: self.keep_hours.unwrap_or(0),
+            days: self.keep_days.unwrap_or(0),
+            weeks: self.keep_weeks.unwrap_or(0),
+        })
+    }
+
+    pub fn storage(&self) -> crate::Storage {
+        self.storage.unwrap_or_default()
+    }
+
+    pub fn keep_alive(&self) -> bool {
+        self.keep_alive.unwrap_or(false)
+    }
+
+    pub fn include(&self) -> &[String] {
+        self.include.as_deref().unwrap_or(&[])
+    }
+
+    pub fn exclude(&self) -> &[String] {
+        self.exclude.as_deref().unwrap_or(&[])
+    }
 }
 
 impl std::fmt::Display for RepoConfig {
@@ -224,6 +403,22 @@ impl std::fmt::Display for RepoConfig {
                 branch
             )?;
         }
+        if let Some(push_remote) = self.push_remote() {
+            writeln!(
+                f,
+                "\t{}={}",
+                PUSH_REMOTE_FIELD.split_once('.').unwrap().1,
+                push_remote
+            )?;
+        }
+        if let Some(pull_remote) = self.pull_remote() {
+            writeln!(
+                f,
+                "\t{}={}",
+                PULL_REMOTE_FIELD.split_once('.').unwrap().1,
+                pull_remote
+            )?;
+        }
         writeln!(f, "[{}]", BACKUP_CAPACITY_FIELD.split_once('.').unwrap().0)?;
         writeln!(
             f,
@@ -231,6 +426,43 @@ impl std::fmt::Display for RepoConfig {
             BACKUP_CAPACITY_FIELD.split_once('.').unwrap().1,
             self.capacity().unwrap_or(0)
         )?;
+        if let Some(retention) = self.retention() {
+            for (field, keep) in [
+                (KEEP_HOURS_FIELD, retention.hours),
+                (KEEP_DAYS_FIELD, retention.days),
+                (KEEP_WEEKS_FIELD, retention.weeks),
+            ] {
+                writeln!(f, "\t{}={}", field.split_once('.').unwrap().1, keep)?;
+            }
+        }
+        writeln!(
+            f,
+            "\t{}={}",
+            STORAGE_FIELD.split_once('.').unwrap().1,
+            self.storage()
+        )?;
+        writeln!(
+            f,
+            "\t{}={}",
+            KEEP_ALIVE_FIELD.split_once('.').unwrap().1,
+            self.keep_alive()
+        )?;
+        for pattern in self.include() {
+            writeln!(
+                f,
+                "\t{}={}",
+                INCLUDE_FIELD.split_once('.').unwrap().1,
+                pattern
+            )?;
+        }
+        for pattern in self.exclude() {
+            writeln!(
+                f,
+                "\t{}={}",
+                EXCLUDE_FIELD.split_once('.').unwrap().1,
+                pattern
+            )?;
+        }
         Ok(())
     }
 }
@@ -239,6 +471,52 @@ fn git_dir_config(repo: &git2::Repository) -> std::path::PathBuf {
     repo.path().join("config")
 }
 
+fn get_multivar(config: &git2::Config, name: &str) -> Option<Vec<String>> {
+    config
+        .multivar(name, None)
+        .map(|entries| {
+            let mut values = Vec::new();
+            entries
+                .for_each(|entry| {
+                    if let Ok(value) = entry.value() {
+                        values.push(value.to_owned());
+                    }
+                })
+                .unwrap();
+            if values.is_empty() {
+                None
+            } else {
+                Some(values)
+            }
+        })
+        .unwrap_or(None)
+}
+
+fn parse_storage(value: &str) -> Option<crate::Storage> {
+    match value.parse() {
+        Ok(storage) => Some(storage),
+        Err(err) => {
+            log::warn!("Invalid {STORAGE_FIELD}: {err}");
+            None
+        }
+    }
+}
+
+fn keep_count(field: &str, value: i64) -> Option<u64> {
+    let keep = u64::try_from(value).ok();
+    if keep.is_none() {
+        log::warn!("Ignoring {field}={value}, expected a non-negative count");
+    }
+    keep
+}
+
+fn parse_bool(value: &str) -> bool {
+    matches!(
+        value.to_ascii_lowercase().as_str(),
+        "true" | "yes" | "on" | "1" | ""
+    )
+}
+
 fn default_branch(config: &git2::Config) -> &str {
     config.get_str("init.defaultBranch").ok().unwrap_or("main")
 }
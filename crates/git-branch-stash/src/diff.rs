@@ -0,0 +1,120 @@
+/// Branch changes between two [`Snapshot`][crate::Snapshot]s
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Diff {
+    pub branches: Vec<BranchDiff>,
+}
+
+impl Diff {
+    /// Compare the branches of two snapshots
+    pub fn new(old: &crate::Snapshot, new: &crate::Snapshot) -> Self {
+        let mut branches: std::collections::BTreeMap<&str, BranchDiff> = Default::default();
+        for branch in old.branches.iter() {
+            let diff = branches
+                .entry(branch.name.as_str())
+                .or_insert_with(|| BranchDiff::new(&branch.name));
+            diff.old_id = Some(branch.id);
+            diff.old_push = branch.push.clone();
+        }
+        for branch in new.branches.iter() {
+            let diff = branches
+                .entry(branch.name.as_str())
+                .or_insert_with(|| BranchDiff::new(&branch.name));
+            diff.new_id = Some(branch.id);
+            diff.new_push = branch.push.clone();
+        }
+        let branches = branches
+            .into_values()
+            .filter(|b| b.old_id != b.new_id)
+            .collect();
+        Self { branches }
+    }
+
+    /// Check if the snapshots have the same branches
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+
+    /// Count the commits gained and lost by each moved branch
+    ///
+    /// Branches with commits missing from the repo are left uncounted.
+    pub fn count_commits(&mut self, repo: &crate::git::GitRepo) {
+        for branch in self.branches.iter_mut() {
+            if let (Some(old_id), Some(new_id)) = (branch.old_id, branch.new_id) {
+                match repo.raw().graph_ahead_behind(new_id, old_id) {
+                    Ok((ahead, behind)) => {
+                        branch.ahead = Some(ahead);
+                        branch.behind = Some(behind);
+                    }
+                    Err(err) => {
+                        log::debug!("Failed to compare {}: {}", branch.name, err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Change to an individual branch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BranchDiff {
+    pub name: String,
+    pub old_id: Option<git2::Oid>,
+    pub new_id: Option<git2::Oid>,
+    /// Commits reachable from `new_id` but not `old_id`
+    pub ahead: Option<usize>,
+    /// Commits reachable from `old_id` but not `new_id`
+    pub behind: Option<usize>,
+    /// Where the branch had been pushed to, as of `old_id`
+    pub old_push: Option<crate::RemoteBranch>,
+    /// Where the branch had been pushed to, as of `new_id`
+    pub new_push: Option<crate::RemoteBranch>,
+}
+
+impl BranchDiff {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            old_id: None,
+            new_id: None,
+            ahead: None,
+            behind: None,
+            old_push: None,
+            new_push: None,
+        }
+    }
+
+    pub fn status(&self) -> DiffStatus {
+        match (self.old_id, self.new_id) {
+            (None, _) => DiffStatus::Added,
+            (Some(_), Some(_)) => DiffStatus::Moved,
+            (Some(_), None) => DiffStatus::Removed,
+        }
+    }
+}
+
+impl serde::Serialize for BranchDiff {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("BranchDiff", 8)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("status", &self.status())?;
+        state.serialize_field("old_id", &self.old_id.map(|id| id.to_string()))?;
+        state.serialize_field("new_id", &self.new_id.map(|id| id.to_string()))?;
+        state.serialize_field("ahead", &self.ahead)?;
+        state.serialize_field("behind", &self.behind)?;
+        state.serialize_field("old_push", &self.old_push)?;
+        state.serialize_field("new_push", &self.new_push)?;
+        state.end()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    Added,
+    Removed,
+    Moved,
+}
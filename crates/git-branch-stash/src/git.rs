@@ -9,19 +9,27 @@ pub(crate) struct Branch {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub(crate) struct Commit {
+pub(crate) struct Tag {
+    pub(crate) name: String,
     pub(crate) id: git2::Oid,
-    pub(crate) tree_id: git2::Oid,
-    pub(crate) summary: bstr::BString,
-    pub(crate) time: std::time::SystemTime,
-    pub(crate) author: Option<std::rc::Rc<str>>,
-    pub(crate) committer: Option<std::rc::Rc<str>>,
+    pub(crate) annotated: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Commit {
+    pub id: git2::Oid,
+    pub tree_id: git2::Oid,
+    pub summary: bstr::BString,
+    pub time: std::time::SystemTime,
+    pub author: Option<std::rc::Rc<str>>,
+    pub committer: Option<std::rc::Rc<str>>,
 }
 
 pub struct GitRepo {
     repo: git2::Repository,
     push_remote: Option<String>,
     pull_remote: Option<String>,
+    config: std::cell::OnceCell<Option<git2::Config>>,
     commits: std::cell::RefCell<std::collections::HashMap<git2::Oid, std::rc::Rc<Commit>>>,
     interned_strings: std::cell::RefCell<std::collections::HashSet<std::rc::Rc<str>>>,
 }
@@ -32,17 +40,51 @@ impl GitRepo {
             repo,
             push_remote: None,
             pull_remote: None,
+            config: Default::default(),
             commits: Default::default(),
             interned_strings: Default::default(),
         }
     }
 
-    pub(crate) fn push_remote(&self) -> &str {
-        self.push_remote.as_deref().unwrap_or("origin")
+    pub fn set_push_remote(&mut self, remote: &str) {
+        self.push_remote = Some(remote.to_owned());
+    }
+
+    pub fn set_pull_remote(&mut self, remote: &str) {
+        self.pull_remote = Some(remote.to_owned());
     }
 
-    pub(crate) fn pull_remote(&self) -> &str {
-        self.pull_remote.as_deref().unwrap_or("origin")
+    /// Remote that `branch` is pushed to, falling back to git's own config and then `origin`
+    pub(crate) fn push_remote(&self, branch: &str) -> String {
+        if let Some(remote) = self.push_remote.as_deref() {
+            return remote.to_owned();
+        }
+        let get = |key: &str| self.config()?.get_string(key).ok();
+        get(&format!("branch.{branch}.pushRemote"))
+            .or_else(|| get("remote.pushDefault"))
+            .or_else(|| get(&format!("branch.{branch}.remote")))
+            .unwrap_or_else(|| "origin".to_owned())
+    }
+
+    /// Remote that `branch` is pulled from, falling back to git's own config and then `origin`
+    pub(crate) fn pull_remote(&self, branch: &str) -> String {
+        if let Some(remote) = self.pull_remote.as_deref() {
+            return remote.to_owned();
+        }
+        let get = |key: &str| self.config()?.get_string(key).ok();
+        get(&format!("branch.{branch}.remote")).unwrap_or_else(|| "origin".to_owned())
+    }
+
+    /// Read-only view of git's config, loaded once
+    fn config(&self) -> Option<&git2::Config> {
+        self.config
+            .get_or_init(|| {
+                self.repo
+                    .config()
+                    .and_then(|mut config| config.snapshot())
+                    .ok()
+            })
+            .as_ref()
     }
 
     pub fn raw(&self) -> &git2::Repository {
@@ -53,7 +95,7 @@ impl GitRepo {
         &mut self.repo
     }
 
-    pub(crate) fn find_commit(&self, id: git2::Oid) -> Option<std::rc::Rc<Commit>> {
+    pub fn find_commit(&self, id: git2::Oid) -> Option<std::rc::Rc<Commit>> {
         let mut commits = self.commits.borrow_mut();
         if let Some(commit) = commits.get(&id) {
             Some(std::rc::Rc::clone(commit))
@@ -86,7 +128,7 @@ impl GitRepo {
         let push_id = self
             .repo
             .find_branch(
-                &format!("{}/{}", self.push_remote(), name),
+                &format!("{}/{}", self.push_remote(name), name),
                 git2::BranchType::Remote,
             )
             .ok()
@@ -94,7 +136,7 @@ impl GitRepo {
         let pull_id = self
             .repo
             .find_branch(
-                &format!("{}/{}", self.pull_remote(), name),
+                &format!("{}/{}", self.pull_remote(name), name),
                 git2::BranchType::Remote,
             )
             .ok()
@@ -108,12 +150,80 @@ impl GitRepo {
         })
     }
 
+    /// Whether `needle_id` is in the history of `haystack_id`
+    pub(crate) fn contains_commit(&self, haystack_id: git2::Oid, needle_id: git2::Oid) -> bool {
+        haystack_id == needle_id
+            || self
+                .repo
+                .graph_descendant_of(haystack_id, needle_id)
+                .unwrap_or(false)
+    }
+
     pub(crate) fn branch(&mut self, name: &str, id: git2::Oid) -> Result<(), git2::Error> {
         let commit = self.repo.find_commit(id)?;
         self.repo.branch(name, &commit, true)?;
         Ok(())
     }
 
+    /// The `branch.<name>.*` config, keyed by variable
+    pub(crate) fn branch_config(&self, name: &str) -> std::collections::BTreeMap<String, String> {
+        let mut values = std::collections::BTreeMap::new();
+        let prefix = format!("branch.{name}.");
+        let Some(config) = self.config() else {
+            return values;
+        };
+        let Ok(entries) = config.entries(Some("branch\\..*")) else {
+            return values;
+        };
+        let _ = entries.for_each(|entry| {
+            if let (Ok(key), Ok(value)) = (entry.name(), entry.value())
+                && let Some(var) = key.strip_prefix(&prefix)
+                && !var.contains('.')
+            {
+                values.insert(var.to_owned(), value.to_owned());
+            }
+        });
+        values
+    }
+
+    pub(crate) fn set_branch_config(
+        &mut self,
+        name: &str,
+        values: &std::collections::BTreeMap<String, String>,
+    ) -> Result<(), git2::Error> {
+        let mut config = self.repo.config()?;
+        for (var, value) in values {
+            config.set_str(&format!("branch.{name}.{var}"), value)?;
+        }
+        self.config.take();
+        Ok(())
+    }
+
+    pub(crate) fn find_reference_id(&self, ref_name: &str) -> Option<git2::Oid> {
+        self.repo.find_reference(ref_name).ok()?.target()
+    }
+
+    pub(crate) fn set_reference(
+        &mut self,
+        ref_name: &str,
+        id: git2::Oid,
+    ) -> Result<(), git2::Error> {
+        self.repo
+            .reference(ref_name, id, true, "git-branch-stash: restore")?;
+        Ok(())
+    }
+
+    pub(crate) fn delete_reference(&mut self, ref_name: &str) -> Result<(), git2::Error> {
+        self.repo.find_reference(ref_name)?.delete()?;
+        Ok(())
+    }
+
+    pub(crate) fn delete_branch(&mut self, name: &str) -> Result<(), git2::Error> {
+        let mut branch = self.repo.find_branch(name, git2::BranchType::Local)?;
+        branch.delete()?;
+        Ok(())
+    }
+
     pub(crate) fn find_local_branch(&self, name: &str) -> Option<Branch> {
         let branch = self.repo.find_branch(name, git2::BranchType::Local).ok()?;
         let id = branch.get().target().unwrap();
@@ -121,7 +231,7 @@ impl GitRepo {
         let push_id = self
             .repo
             .find_branch(
-                &format!("{}/{}", self.push_remote(), name),
+                &format!("{}/{}", self.push_remote(name), name),
                 git2::BranchType::Remote,
             )
             .ok()
@@ -129,7 +239,7 @@ impl GitRepo {
         let pull_id = self
             .repo
             .find_branch(
-                &format!("{}/{}", self.pull_remote(), name),
+                &format!("{}/{}", self.pull_remote(name), name),
                 git2::BranchType::Remote,
             )
             .ok()
@@ -143,6 +253,34 @@ impl GitRepo {
         })
     }
 
+    pub(crate) fn tags(&self) -> impl Iterator<Item = Tag> + '_ {
+        log::trace!("Loading tags");
+        self.repo
+            .references_glob("refs/tags/*")
+            .into_iter()
+            .flatten()
+            .filter_map(move |reference| {
+                let reference = reference.ok()?;
+                let name = if let Ok(name) = reference.shorthand() {
+                    name
+                } else {
+                    log::debug!(
+                        "Ignoring non-UTF8 tag {:?}",
+                        reference.name_bytes().as_bstr()
+                    );
+                    return None;
+                };
+                let id = reference.target()?;
+                let annotated = self.repo.find_tag(id).is_ok();
+
+                Some(Tag {
+                    name: name.to_owned(),
+                    id,
+                    annotated,
+                })
+            })
+    }
+
     pub(crate) fn local_branches(&self) -> impl Iterator<Item = Branch> + '_ {
         log::trace!("Loading branches");
         self.repo
@@ -165,7 +303,7 @@ impl GitRepo {
                 let push_id = self
                     .repo
                     .find_branch(
-                        &format!("{}/{}", self.push_remote(), name),
+                        &format!("{}/{}", self.push_remote(name), name),
                         git2::BranchType::Remote,
                     )
                     .ok()
@@ -173,7 +311,7 @@ impl GitRepo {
                 let pull_id = self
                     .repo
                     .find_branch(
-                        &format!("{}/{}", self.pull_remote(), name),
+                        &format!("{}/{}", self.pull_remote(name), name),
                         git2::BranchType::Remote,
                     )
                     .ok()
@@ -201,6 +339,70 @@ impl GitRepo {
         Ok(())
     }
 
+    /// The ref HEAD points to or, when detached, the commit
+    pub(crate) fn head_target(&self) -> Option<String> {
+        head_target(&self.repo)
+    }
+
+    /// [`GitRepo::head_target`] for every worktree besides this one
+    pub(crate) fn other_worktrees(&self) -> Vec<(std::path::PathBuf, String)> {
+        let current = self.repo.workdir().and_then(|p| p.canonicalize().ok());
+        let mut repos = Vec::new();
+        if self.repo.is_worktree() {
+            match git2::Repository::open(self.repo.commondir()) {
+                Ok(repo) => repos.push(repo),
+                Err(err) => log::debug!("Failed to open main worktree: {err}"),
+            }
+        }
+        let names = self.repo.worktrees();
+        for name in names
+            .iter()
+            .flat_map(|n| n.iter().filter_map(|n| n.ok().flatten()))
+        {
+            match self
+                .repo
+                .find_worktree(name)
+                .and_then(|w| git2::Repository::open_from_worktree(&w))
+            {
+                Ok(repo) => repos.push(repo),
+                Err(err) => log::debug!("Failed to open worktree {name}: {err}"),
+            }
+        }
+
+        repos
+            .iter()
+            .filter_map(|repo| {
+                let path = repo.workdir()?.canonicalize().ok()?;
+                if Some(&path) == current.as_ref() {
+                    return None;
+                }
+                let head = head_target(repo)?;
+                Some((path, head))
+            })
+            .collect()
+    }
+
+    pub(crate) fn checkout_detached(&mut self, id: git2::Oid) -> Result<(), git2::Error> {
+        self.repo.set_head_detached(id)?;
+        let mut builder = git2::build::CheckoutBuilder::new();
+        builder.force();
+        self.repo.checkout_head(Some(&mut builder))?;
+        Ok(())
+    }
+
+    /// Point HEAD back at a [`GitRepo::head_target`], checking out the working tree
+    pub(crate) fn restore_head(&mut self, target: &str) -> Result<(), git2::Error> {
+        if target.starts_with("refs/") {
+            self.repo.set_head(target)?;
+        } else {
+            self.repo.set_head_detached(git2::Oid::from_str(target)?)?;
+        }
+        let mut builder = git2::build::CheckoutBuilder::new();
+        builder.force();
+        self.repo.checkout_head(Some(&mut builder))?;
+        Ok(())
+    }
+
     pub(crate) fn switch(&mut self, name: &str) -> Result<(), git2::Error> {
         // HACK: We shouldn't limit ourselves to `Local`
         let branch = self.repo.find_branch(name, git2::BranchType::Local)?;
@@ -223,6 +425,14 @@ impl GitRepo {
     }
 }
 
+fn head_target(repo: &git2::Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    match head.symbolic_target().ok()? {
+        Some(target) => Some(target.to_owned()),
+        None => head.target().map(|id| id.to_string()),
+    }
+}
+
 impl std::fmt::Debug for GitRepo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         f.debug_struct("GitRepo")
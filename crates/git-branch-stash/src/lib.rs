@@ -4,11 +4,32 @@
 
 pub mod config;
 
+pub use diff::BranchDiff;
+pub use diff::Diff;
+pub use diff::DiffStatus;
+pub use git::Commit;
 pub use git::GitRepo;
+pub use pattern::BranchPatterns;
+pub use plan::ChangeKind;
+pub use plan::Movement;
+pub use plan::Plan;
+pub use plan::PlannedChange;
+pub use plan::RefKind;
+pub use snapshot::ApplyOptions;
 pub use snapshot::Branch;
+pub use snapshot::Head;
+pub use snapshot::RemoteBranch;
 pub use snapshot::Snapshot;
+pub use snapshot::SnapshotOptions;
+pub use snapshot::Tag;
+pub use stack::Location;
+pub use stack::Retention;
 pub use stack::Stack;
+pub use stack::Storage;
 
+mod diff;
 mod git;
+mod pattern;
+mod plan;
 mod snapshot;
 mod stack;
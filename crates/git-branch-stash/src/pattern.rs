@@ -0,0 +1,103 @@
+/// Match branch names against gitignore-style patterns, like `release/*`
+#[derive(Clone, Debug)]
+pub struct BranchPatterns {
+    ignores: ignore::gitignore::Gitignore,
+    names: std::collections::HashSet<String>,
+    full_names: bool,
+}
+
+impl BranchPatterns {
+    pub fn new<'p>(patterns: impl IntoIterator<Item = &'p str>) -> eyre::Result<Self> {
+        let mut ignores = ignore::gitignore::GitignoreBuilder::new("");
+        for pattern in patterns {
+            ignores.add_line(None, pattern)?;
+        }
+        let ignores = ignores.build()?;
+        Ok(Self {
+            ignores,
+            names: Default::default(),
+            full_names: false,
+        })
+    }
+
+    /// Match whole branch names, rather than a name at any depth or any of its parents
+    ///
+    /// Plain names are compared literally and globs are anchored to the start of the name.
+    pub fn full_names<'p>(patterns: impl IntoIterator<Item = &'p str>) -> eyre::Result<Self> {
+        let mut ignores = ignore::gitignore::GitignoreBuilder::new("");
+        let mut names = std::collections::HashSet::new();
+        for pattern in patterns {
+            if !pattern.contains(['*', '?', '[', '\\', '!']) {
+                names.insert(pattern.to_owned());
+            } else if let Some(negated) = pattern.strip_prefix('!') {
+                ignores.add_line(None, &format!("!/{}", negated.trim_start_matches('/')))?;
+            } else {
+                ignores.add_line(None, &format!("/{}", pattern.trim_start_matches('/')))?;
+            }
+        }
+        let ignores = ignores.build()?;
+        Ok(Self {
+            ignores,
+            names,
+            full_names: true,
+        })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        if self.names.contains(name) {
+            return true;
+        }
+        let name_match = if self.full_names {
+            self.ignores.matched(name, false)
+        } else {
+            self.ignores.matched_path_or_any_parents(name, false)
+        };
+        match name_match {
+            ignore::Match::None => false,
+            ignore::Match::Ignore(_) => true,
+            ignore::Match::Whitelist(_) => false,
+        }
+    }
+}
+
+impl Default for BranchPatterns {
+    fn default() -> Self {
+        Self {
+            ignores: ignore::gitignore::Gitignore::empty(),
+            names: Default::default(),
+            full_names: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn full_names_exact() {
+        let patterns = BranchPatterns::full_names(["fix"]).unwrap();
+        assert!(patterns.is_match("fix"));
+        assert!(!patterns.is_match("user/fix"));
+        assert!(!patterns.is_match("fix/x"));
+        assert!(!patterns.is_match("fix-2"));
+    }
+
+    #[test]
+    fn full_names_glob() {
+        let patterns = BranchPatterns::full_names(["fix-*", "release/*"]).unwrap();
+        assert!(patterns.is_match("fix-2"));
+        assert!(!patterns.is_match("fix-2/x"));
+        assert!(!patterns.is_match("user/fix-2"));
+        assert!(patterns.is_match("release/1.0"));
+        assert!(!patterns.is_match("user/release/1.0"));
+    }
+
+    #[test]
+    fn any_depth() {
+        let patterns = BranchPatterns::new(["fix"]).unwrap();
+        assert!(patterns.is_match("fix"));
+        assert!(patterns.is_match("user/fix"));
+        assert!(patterns.is_match("fix/x"));
+    }
+}
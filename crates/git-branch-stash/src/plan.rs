@@ -0,0 +1,299 @@
+/// Changes needed to restore a [`Snapshot`][crate::Snapshot]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub changes: Vec<PlannedChange>,
+    /// Changes left out because the branch is protected
+    pub skipped: Vec<PlannedChange>,
+    /// Some branches in the snapshot were not selected by [`ApplyOptions::branches`][crate::ApplyOptions::branches]
+    pub partial: bool,
+    /// `branch.<name>.*` config to write for created branches
+    pub config: std::collections::BTreeMap<String, std::collections::BTreeMap<String, String>>,
+    /// Where to point HEAD once the refs are restored
+    pub head: Option<crate::Head>,
+    /// Stash commit of uncommitted changes to pop over the working tree once the refs are restored
+    pub worktree: Option<git2::Oid>,
+}
+
+impl Plan {
+    /// Check if there is nothing to apply
+    ///
+    /// Protected branches may still differ, see [`Plan::skipped`].
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.head.is_none()
+    }
+
+    /// Restore branches alongside the existing ones, as `<prefix><name><suffix>`
+    ///
+    /// Nothing is modified or deleted and HEAD is left alone.  Protected branches can be restored
+    /// this way since they aren't modified.
+    ///
+    /// Fails rather than overwrite an existing `<prefix><name><suffix>` at a different commit.
+    pub fn renamed(
+        &self,
+        repo: &crate::git::GitRepo,
+        prefix: &str,
+        suffix: &str,
+    ) -> Result<Self, git2::Error> {
+        let mut changes = Vec::new();
+        let mut taken = Vec::new();
+        for change in self.changes.iter().chain(self.skipped.iter()) {
+            if change.ref_kind != RefKind::Branch {
+                continue;
+            }
+            let Some(new_id) = change.new_id else {
+                continue;
+            };
+            let name = format!("{prefix}{}{suffix}", change.name);
+            match repo.find_local_branch(&name).map(|b| b.id) {
+                None => changes.push(PlannedChange {
+                    name,
+                    old_id: None,
+                    new_id: Some(new_id),
+                    head: false,
+                    movement: None,
+                    ref_kind: RefKind::Branch,
+                }),
+                Some(old_id) if old_id == new_id => {
+                    log::trace!("No change for {name}");
+                }
+                Some(_) => taken.push(name),
+            }
+        }
+        if !taken.is_empty() {
+            return Err(git2::Error::new(
+                git2::ErrorCode::Exists,
+                git2::ErrorClass::Reference,
+                format!("branches already exist: {}", taken.join(", ")),
+            ));
+        }
+        Ok(Self {
+            changes,
+            skipped: Vec::new(),
+            partial: self.partial,
+            config: Default::default(),
+            head: None,
+            worktree: self.worktree,
+        })
+    }
+
+    /// Fail if any branch to change is checked out in another worktree
+    pub fn check_worktrees(&self, repo: &crate::git::GitRepo) -> Result<(), git2::Error> {
+        let worktrees = repo.other_worktrees();
+        let checked_out: Vec<_> = self
+            .changes
+            .iter()
+            .filter(|c| c.ref_kind == RefKind::Branch)
+            .filter_map(|c| {
+                let ref_name = c.ref_name();
+                let (path, _) = worktrees.iter().find(|(_, head)| *head == ref_name)?;
+                Some(format!("{} ({})", c.name, path.display()))
+            })
+            .collect();
+        if !checked_out.is_empty() {
+            return Err(git2::Error::new(
+                git2::ErrorCode::Locked,
+                git2::ErrorClass::Worktree,
+                format!(
+                    "branches are checked out in other worktrees: {}",
+                    checked_out.join(", ")
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Update the repo according to the plan
+    pub fn apply(&self, repo: &mut crate::git::GitRepo) -> Result<(), git2::Error> {
+        self.check_worktrees(repo)?;
+        let ref_names: Vec<_> = self.changes.iter().map(|c| c.ref_name()).collect();
+        let transaction_changes: Vec<_> = self
+            .changes
+            .iter()
+            .zip(ref_names.iter())
+            .map(|(change, ref_name)| {
+                (
+                    change.old_id.unwrap_or(git2::Oid::ZERO_SHA1),
+                    change.new_id.unwrap_or(git2::Oid::ZERO_SHA1),
+                    ref_name.as_str(),
+                )
+            })
+            .collect();
+        let transaction_repo = git2::Repository::open(repo.raw().path())?;
+        let hooks = git2_ext::hooks::Hooks::with_repo(&transaction_repo)?;
+        let transaction = hooks
+            .run_reference_transaction(&transaction_repo, &transaction_changes)
+            .map_err(|err| {
+                git2::Error::new(
+                    git2::ErrorCode::GenericError,
+                    git2::ErrorClass::Callback,
+                    err.to_string(),
+                )
+            })?;
+
+        for (i, change) in self.changes.iter().enumerate() {
+            if let Err(err) = change.apply(repo) {
+                log::debug!("Failed to restore {}, rolling back: {}", change.name, err);
+                roll_back(repo, &self.changes[..=i]);
+                // Dropping `transaction` reports the abort to the hook
+                return Err(err);
+            }
+        }
+
+        let original_head = repo.head_target();
+        let switched = match self.head.as_ref() {
+            Some(crate::Head::Branch(name)) => {
+                log::debug!("Switching to {name}");
+                repo.switch(name)
+            }
+            Some(crate::Head::Detached(id)) => {
+                log::debug!("Detaching HEAD at {id}");
+                repo.checkout_detached(*id)
+            }
+            None => Ok(()),
+        };
+        if let Err(err) = switched {
+            log::debug!("Failed to update HEAD, rolling back: {err}");
+            // HEAD has to be off of any branch the roll back deletes
+            if let Some(original_head) = original_head
+                && let Err(err) = repo.restore_head(&original_head)
+            {
+                log::error!("Failed to roll back HEAD: {err}");
+            }
+            roll_back(repo, &self.changes);
+            return Err(err);
+        }
+
+        transaction.committed();
+
+        for (name, values) in self.config.iter() {
+            if let Err(err) = repo.set_branch_config(name, values) {
+                log::warn!("Failed to restore config for {name}: {err}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Undo `applied` changes, most recent first
+fn roll_back(repo: &mut crate::git::GitRepo, applied: &[PlannedChange]) {
+    for applied in applied.iter().rev() {
+        let current_id = repo.find_reference_id(&applied.ref_name());
+        if current_id == applied.old_id && !applied.head {
+            continue;
+        }
+        if let Err(err) = applied.inverse().apply(repo) {
+            log::error!("Failed to roll back {}: {}", applied.name, err);
+        }
+    }
+}
+
+/// Update to an individual branch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlannedChange {
+    pub name: String,
+    /// Where the branch currently points, if it exists
+    pub old_id: Option<git2::Oid>,
+    /// Where the branch will point, if it is kept
+    pub new_id: Option<git2::Oid>,
+    /// HEAD is on this branch, so the working tree will be checked out
+    pub head: bool,
+    /// How an existing branch moves, if it is updated
+    pub movement: Option<Movement>,
+    pub ref_kind: RefKind,
+}
+
+impl PlannedChange {
+    pub fn kind(&self) -> ChangeKind {
+        match (self.old_id, self.new_id) {
+            (None, _) => ChangeKind::Create,
+            (Some(_), Some(_)) => ChangeKind::Update,
+            (Some(_), None) => ChangeKind::Delete,
+        }
+    }
+
+    /// Fully qualified name of the branch's reference
+    pub fn ref_name(&self) -> String {
+        match self.ref_kind {
+            RefKind::Branch => format!("refs/heads/{}", self.name),
+            RefKind::Remote => format!("refs/remotes/{}", self.name),
+            RefKind::Tag => format!("refs/tags/{}", self.name),
+        }
+    }
+
+    fn apply(&self, repo: &mut crate::git::GitRepo) -> Result<(), git2::Error> {
+        let name = self.name.as_str();
+        if self.ref_kind != RefKind::Branch {
+            let ref_name = self.ref_name();
+            match self.new_id {
+                None => {
+                    log::debug!("Deleting {ref_name}");
+                    repo.delete_reference(&ref_name)?;
+                }
+                Some(new_id) => {
+                    log::debug!("Restoring {ref_name}");
+                    repo.set_reference(&ref_name, new_id)?;
+                }
+            }
+            return Ok(());
+        }
+        match self.new_id {
+            None => {
+                log::debug!("Deleting {name}");
+                repo.delete_branch(name)?;
+            }
+            Some(new_id) if self.head => {
+                log::debug!("Restoring {name} (HEAD)");
+                repo.detach()?;
+                repo.branch(name, new_id)?;
+                repo.switch(name)?;
+            }
+            Some(new_id) => {
+                log::debug!("Restoring {name}");
+                repo.branch(name, new_id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The change that undoes this one
+    fn inverse(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            old_id: self.new_id,
+            new_id: self.old_id,
+            head: self.head,
+            movement: None,
+            ref_kind: self.ref_kind,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKind {
+    Create,
+    Update,
+    Delete,
+}
+
+/// Which namespace a [`PlannedChange::name`] is in
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RefKind {
+    /// `refs/heads/<name>`
+    Branch,
+    /// `refs/remotes/<name>`, where the name includes the remote
+    Remote,
+    /// `refs/tags/<name>`
+    Tag,
+}
+
+/// How an updated branch relates to where it is moving
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Movement {
+    /// The branch only gains commits
+    FastForward,
+    /// The branch drops commits, but they are all still in the snapshot
+    Rewind,
+    /// The branch drops commits the snapshot never saw
+    Divergent,
+}
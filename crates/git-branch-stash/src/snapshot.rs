@@ -2,6 +2,16 @@
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Snapshot {
     pub branches: Vec<Branch>,
+    /// Tags, when they were captured
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<Tag>>,
+    /// Stash commit holding uncommitted changes, when they were captured
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(serialize_with = "serialize_opt_oid")]
+    #[serde(deserialize_with = "deserialize_opt_oid")]
+    pub worktree: Option<git2::Oid>,
     #[serde(default)]
     #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
     pub metadata: std::collections::BTreeMap<String, serde_json::Value>,
@@ -25,13 +35,34 @@ impl Snapshot {
 
     /// Extract branch state from an existing repo
     pub fn from_repo(repo: &crate::git::GitRepo) -> Result<Self, git2::Error> {
+        Self::from_repo_with(repo, &SnapshotOptions::default())
+    }
+
+    /// Extract the state of the selected branches from an existing repo
+    pub fn from_repo_with(
+        repo: &crate::git::GitRepo,
+        options: &SnapshotOptions,
+    ) -> Result<Self, git2::Error> {
+        let is_selected = options.matcher()?;
         let mut branches: Vec<_> = repo
             .local_branches()
+            .filter(|b| is_selected(&b.name))
             .map(|b| {
                 let commit = repo.find_commit(b.id).unwrap();
+                let push = b.push_id.map(|id| RemoteBranch {
+                    name: format!("{}/{}", repo.push_remote(&b.name), b.name),
+                    id,
+                });
+                let pull = b.pull_id.map(|id| RemoteBranch {
+                    name: format!("{}/{}", repo.pull_remote(&b.name), b.name),
+                    id,
+                });
                 Branch {
+                    config: repo.branch_config(&b.name),
                     name: b.name,
                     id: b.id,
+                    push,
+                    pull,
                     metadata: maplit::btreemap! {
                         "summary".to_owned() => serde_json::Value::String(
                             String::from_utf8_lossy(commit.summary.as_slice()).into_owned()
@@ -41,54 +72,442 @@ impl Snapshot {
             })
             .collect();
         branches.sort_unstable();
-        let metadata = Default::default();
-        Ok(Self { branches, metadata })
+        let mut metadata = std::collections::BTreeMap::new();
+        if let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+            metadata.insert("timestamp".to_owned(), serde_json::json!(now.as_secs()));
+        }
+        if let Some(head) = repo.head_target() {
+            metadata.insert("head".to_owned(), serde_json::Value::String(head));
+        }
+        let worktrees: serde_json::Map<_, _> = repo
+            .other_worktrees()
+            .into_iter()
+            .map(|(path, head)| (path.display().to_string(), serde_json::Value::String(head)))
+            .collect();
+        if !worktrees.is_empty() {
+            metadata.insert("worktrees".to_owned(), serde_json::Value::Object(worktrees));
+        }
+        for (key, patterns) in [("include", &options.include), ("exclude", &options.exclude)] {
+            if !patterns.is_empty() {
+                metadata.insert(key.to_owned(), serde_json::json!(patterns));
+            }
+        }
+        let tags = options.tags.then(|| {
+            let mut tags: Vec<_> = repo
+                .tags()
+                .map(|t| Tag {
+                    name: t.name,
+                    id: t.id,
+                    annotated: t.annotated,
+                })
+                .collect();
+            tags.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+            tags
+        });
+        Ok(Self {
+            branches,
+            tags,
+            worktree: None,
+            metadata,
+        })
+    }
+
+    /// When the snapshot was taken
+    pub fn timestamp(&self) -> Option<std::time::SystemTime> {
+        let secs = self.metadata.get("timestamp")?.as_u64()?;
+        std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(secs))
+    }
+
+    /// Whether `other` captured the same state, regardless of when it was taken
+    pub(crate) fn same_as(&self, other: &Snapshot) -> bool {
+        let untimed = |snapshot: &Snapshot| {
+            let mut snapshot = snapshot.clone();
+            snapshot.metadata.remove("timestamp");
+            snapshot
+        };
+        untimed(self) == untimed(other)
+    }
+
+    /// Where HEAD pointed when the snapshot was taken
+    pub fn head(&self) -> Option<Head> {
+        let head = self.metadata.get("head")?.as_str()?;
+        match head.strip_prefix("refs/heads/") {
+            Some(name) => Some(Head::Branch(name.to_owned())),
+            None => git2::Oid::from_str(head).ok().map(Head::Detached),
+        }
+    }
+
+    /// Which branches were captured in the snapshot
+    pub fn options(&self) -> SnapshotOptions {
+        let patterns = |key: &str| -> Vec<String> {
+            self.metadata
+                .get(key)
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default()
+        };
+        SnapshotOptions {
+            include: patterns("include"),
+            exclude: patterns("exclude"),
+            tags: self.tags.is_some(),
+        }
     }
 
     /// Update repo to match the branch state
     pub fn apply(&self, repo: &mut crate::git::GitRepo) -> Result<(), git2::Error> {
+        self.apply_with(repo, &ApplyOptions::default())
+    }
+
+    /// Update repo to match the branch state, customizing the restore
+    pub fn apply_with(
+        &self,
+        repo: &mut crate::git::GitRepo,
+        options: &ApplyOptions,
+    ) -> Result<(), git2::Error> {
+        let plan = self.plan(repo, options)?;
+        plan.apply(repo)
+    }
+
+    /// Compute the changes needed to update the repo to match the branch state
+    pub fn plan(
+        &self,
+        repo: &crate::git::GitRepo,
+        options: &ApplyOptions,
+    ) -> Result<crate::plan::Plan, git2::Error> {
         let head_branch = repo.head_branch();
         let head_branch_name = head_branch.as_ref().map(|b| b.name.as_str());
 
-        let mut planned_changes = Vec::new();
+        let chosen = (!options.branches.is_empty())
+            .then(|| {
+                crate::BranchPatterns::full_names(options.branches.iter().map(|p| p.as_str()))
+                    .map_err(invalid_pattern)
+            })
+            .transpose()?;
+        let is_chosen = |name: &str| chosen.as_ref().is_none_or(|c| c.is_match(name));
+
+        // Only the branches being restored need their commits
+        let missing: Vec<_> = self
+            .missing(repo)
+            .into_iter()
+            .filter(|b| is_chosen(&b.name))
+            .collect();
+        let missing_worktree = options
+            .worktree
+            .then(|| self.missing_worktree(repo))
+            .flatten();
+        if (!missing.is_empty() || missing_worktree.is_some()) && !options.skip_missing {
+            let missing = missing
+                .iter()
+                .map(|b| format!("{} ({})", b.name, b.id))
+                .chain(missing_worktree.map(|id| format!("uncommitted changes ({id})")))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(git2::Error::new(
+                git2::ErrorCode::NotFound,
+                git2::ErrorClass::Object,
+                format!("snapshot references missing commits: {missing}"),
+            ));
+        }
+
+        let protected = branch_patterns(&options.protected_branches)?;
+        let is_protected = |name: &str| !options.force && protected.is_match(name);
+
+        let mut changes = Vec::new();
+        let mut skipped = Vec::new();
+        let mut partial = false;
         for branch in self.branches.iter() {
+            if !is_chosen(&branch.name) {
+                log::trace!("Not restoring {}, not selected", branch.name);
+                partial = true;
+                continue;
+            }
+            if missing.contains(&branch) {
+                log::warn!(
+                    "Skipping {}, commit {} no longer exists",
+                    branch.name,
+                    branch.id
+                );
+                continue;
+            }
             let existing = repo.find_local_branch(&branch.name);
             if existing.as_ref().map(|b| b.id) == Some(branch.id) {
                 log::trace!("No change for {}", branch.name);
             } else {
-                let existing_id = existing.map(|b| b.id).unwrap_or(git2::Oid::ZERO_SHA1);
-                let new_id = branch.id;
-                planned_changes.push((existing_id, new_id, branch.name.as_str()));
+                let old_id = existing.map(|b| b.id);
+                let change = crate::plan::PlannedChange {
+                    name: branch.name.clone(),
+                    old_id,
+                    new_id: Some(branch.id),
+                    head: head_branch_name == Some(branch.name.as_str()),
+                    movement: old_id.map(|old_id| self.movement(repo, old_id, branch.id)),
+                    ref_kind: crate::plan::RefKind::Branch,
+                };
+                if change.old_id.is_some() && is_protected(&branch.name) {
+                    log::warn!("Not restoring {} (protected)", branch.name);
+                    skipped.push(change);
+                } else {
+                    changes.push(change);
+                }
+            }
+        }
+        if options.prune {
+            let snapshot_names: std::collections::HashSet<_> =
+                self.branches.iter().map(|b| b.name.as_str()).collect();
+            // Branches the snapshot never looked at weren't missing from it
+            let is_selected = self.options().matcher()?;
+            let mut extra: Vec<_> = repo
+                .local_branches()
+                .filter(|b| {
+                    !snapshot_names.contains(b.name.as_str())
+                        && is_selected(&b.name)
+                        && is_chosen(&b.name)
+                })
+                .collect();
+            extra.sort_unstable();
+            for branch in extra {
+                if head_branch_name == Some(branch.name.as_str()) {
+                    log::warn!("Not deleting {} (HEAD)", branch.name);
+                } else {
+                    let change = crate::plan::PlannedChange {
+                        name: branch.name,
+                        old_id: Some(branch.id),
+                        new_id: None,
+                        head: false,
+                        movement: None,
+                        ref_kind: crate::plan::RefKind::Branch,
+                    };
+                    if is_protected(&change.name) {
+                        log::warn!("Not deleting {} (protected)", change.name);
+                        skipped.push(change);
+                    } else {
+                        changes.push(change);
+                    }
+                }
+            }
+        }
+
+        if options.remotes {
+            let mut remotes: Vec<_> = self
+                .branches
+                .iter()
+                .filter(|b| is_chosen(&b.name))
+                .flat_map(|b| b.push.iter().chain(b.pull.iter()))
+                .collect();
+            remotes.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+            remotes.dedup_by(|a, b| a.name == b.name);
+            for remote in remotes {
+                let ref_name = format!("refs/remotes/{}", remote.name);
+                let old_id = repo.find_reference_id(&ref_name);
+                if old_id == Some(remote.id) {
+                    log::trace!("No change for {}", remote.name);
+                } else if repo.find_commit(remote.id).is_none() {
+                    log::warn!(
+                        "Skipping {}, commit {} no longer exists",
+                        remote.name,
+                        remote.id
+                    );
+                } else {
+                    changes.push(crate::plan::PlannedChange {
+                        name: remote.name.clone(),
+                        old_id,
+                        new_id: Some(remote.id),
+                        head: false,
+                        movement: None,
+                        ref_kind: crate::plan::RefKind::Remote,
+                    });
+                }
+            }
+        }
+
+        if options.tags
+            && let Some(tags) = self.tags.as_ref()
+        {
+            let existing: std::collections::BTreeMap<_, _> =
+                repo.tags().map(|t| (t.name, t.id)).collect();
+            for tag in tags {
+                let old_id = existing.get(&tag.name).copied();
+                if old_id == Some(tag.id) {
+                    log::trace!("No change for tag {}", tag.name);
+                } else if repo.raw().find_object(tag.id, None).is_err() {
+                    log::warn!(
+                        "Skipping tag {}, object {} no longer exists",
+                        tag.name,
+                        tag.id
+                    );
+                } else {
+                    changes.push(crate::plan::PlannedChange {
+                        name: tag.name.clone(),
+                        old_id,
+                        new_id: Some(tag.id),
+                        head: false,
+                        movement: None,
+                        ref_kind: crate::plan::RefKind::Tag,
+                    });
+                }
+            }
+            if options.prune {
+                let snapshot_names: std::collections::HashSet<_> =
+                    tags.iter().map(|t| t.name.as_str()).collect();
+                for (name, id) in existing {
+                    if !snapshot_names.contains(name.as_str()) {
+                        changes.push(crate::plan::PlannedChange {
+                            name,
+                            old_id: Some(id),
+                            new_id: None,
+                            head: false,
+                            movement: None,
+                            ref_kind: crate::plan::RefKind::Tag,
+                        });
+                    }
+                }
+            }
+        }
+
+        let worktrees = repo.other_worktrees();
+        let divergent: Vec<_> = changes
+            .iter()
+            .filter(|c| c.movement == Some(crate::plan::Movement::Divergent))
+            .cloned()
+            .collect();
+        if let Some(prefix) = options.backup_prefix.as_deref() {
+            let mut backups = Vec::new();
+            let mut taken = Vec::new();
+            for change in divergent {
+                let name = format!("{prefix}{}", change.name);
+                match repo.find_local_branch(&name).map(|b| b.id) {
+                    None => {
+                        log::info!("Backing up {} as {}", change.name, name);
+                        backups.push(crate::plan::PlannedChange {
+                            name,
+                            old_id: None,
+                            new_id: change.old_id,
+                            head: false,
+                            movement: None,
+                            ref_kind: crate::plan::RefKind::Branch,
+                        });
+                    }
+                    Some(id) if Some(id) == change.old_id => {
+                        log::info!("{} is already backed up as {}", change.name, name);
+                    }
+                    // Overwriting it would lose the tip an earlier backup saved
+                    Some(_) => taken.push(name),
+                }
+            }
+            if !taken.is_empty() {
+                return Err(git2::Error::new(
+                    git2::ErrorCode::Exists,
+                    git2::ErrorClass::Reference,
+                    format!("backup branches already exist: {}", taken.join(", ")),
+                ));
             }
+            // Save the tips before anything overwrites them
+            backups.append(&mut changes);
+            changes = backups;
+        } else if !divergent.is_empty() && !options.allow_divergent {
+            let divergent = divergent
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(git2::Error::new(
+                git2::ErrorCode::Modified,
+                git2::ErrorClass::Reference,
+                format!("branches have commits not in the snapshot: {divergent}"),
+            ));
         }
 
-        let transaction_repo = git2::Repository::open(repo.raw().path())?;
-        let hooks = git2_ext::hooks::Hooks::with_repo(&transaction_repo)?;
-        let transaction = hooks
-            .run_reference_transaction(&transaction_repo, &planned_changes)
-            .map_err(|err| {
-                git2::Error::new(
-                    git2::ErrorCode::GenericError,
-                    git2::ErrorClass::Callback,
-                    err.to_string(),
-                )
-            })?;
-
-        for (_old_id, new_id, name) in &planned_changes {
-            if head_branch_name == Some(name) {
-                log::debug!("Restoring {name} (HEAD)");
-                repo.detach()?;
-                repo.branch(name, *new_id)?;
-                repo.switch(name)?;
+        let head = options.head.then(|| self.head()).flatten().filter(|head| {
+            let (current, exists) = match head {
+                Head::Branch(name) => (
+                    format!("refs/heads/{name}"),
+                    changes.iter().any(|c| {
+                        c.ref_kind == crate::plan::RefKind::Branch
+                            && c.name == *name
+                            && c.new_id.is_some()
+                    }) || repo.find_local_branch(name).is_some(),
+                ),
+                Head::Detached(id) => (id.to_string(), repo.find_commit(*id).is_some()),
+            };
+            if repo.head_target().as_deref() == Some(current.as_str()) {
+                log::trace!("No change for HEAD");
+                false
+            } else if !exists {
+                log::warn!("Not restoring HEAD, {head} is not available");
+                false
+            } else if worktrees.iter().any(|(_, h)| *h == current) {
+                log::warn!("Not restoring HEAD, {head} is checked out in another worktree");
+                false
             } else {
-                log::debug!("Restoring {name}");
-                repo.branch(name, *new_id)?;
+                true
+            }
+        });
+
+        let mut config = std::collections::BTreeMap::new();
+        if options.branch_config {
+            for change in changes.iter().filter(|c| {
+                c.ref_kind == crate::plan::RefKind::Branch && c.kind() == crate::ChangeKind::Create
+            }) {
+                if let Some(branch) = self.branches.iter().find(|b| b.name == change.name)
+                    && !branch.config.is_empty()
+                {
+                    config.insert(branch.name.clone(), branch.config.clone());
+                }
             }
         }
 
-        transaction.committed();
+        let worktree = options.worktree.then_some(self.worktree).flatten();
+        let worktree = if worktree.is_some() && worktree == missing_worktree {
+            log::warn!("Skipping uncommitted changes, commit no longer exists");
+            None
+        } else {
+            worktree
+        };
 
-        Ok(())
+        Ok(crate::plan::Plan {
+            changes,
+            skipped,
+            partial,
+            config,
+            head,
+            worktree,
+        })
+    }
+
+    /// Classify moving a branch from `old_id` to `new_id`
+    fn movement(
+        &self,
+        repo: &crate::git::GitRepo,
+        old_id: git2::Oid,
+        new_id: git2::Oid,
+    ) -> crate::plan::Movement {
+        if repo.contains_commit(new_id, old_id) {
+            crate::plan::Movement::FastForward
+        } else if self
+            .branches
+            .iter()
+            .any(|b| repo.contains_commit(b.id, old_id))
+        {
+            crate::plan::Movement::Rewind
+        } else {
+            crate::plan::Movement::Divergent
+        }
+    }
+
+    /// Branches whose commits are no longer in the repo, e.g. pruned by `git gc`
+    pub fn missing(&self, repo: &crate::git::GitRepo) -> Vec<&Branch> {
+        self.branches
+            .iter()
+            .filter(|b| repo.find_commit(b.id).is_none())
+            .collect()
+    }
+
+    /// The stash commit of uncommitted changes, if it no longer exists
+    pub fn missing_worktree(&self, repo: &crate::git::GitRepo) -> Option<git2::Oid> {
+        self.worktree.filter(|id| repo.find_commit(*id).is_none())
+    }
+
+    /// Compare the branches of this snapshot (old) to `other` (new)
+    pub fn diff(&self, other: &Snapshot) -> crate::diff::Diff {
+        crate::diff::Diff::new(self, other)
     }
 
     /// Add message metadata
@@ -100,6 +519,73 @@ impl Snapshot {
     }
 }
 
+/// Choose which branches [`Snapshot::from_repo_with`] captures
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotOptions {
+    /// Only capture branches matching these globs, or all branches when empty
+    pub include: Vec<String>,
+    /// Never capture branches matching these globs
+    pub exclude: Vec<String>,
+    /// Capture tags as well
+    pub tags: bool,
+}
+
+impl SnapshotOptions {
+    fn matcher(&self) -> Result<impl Fn(&str) -> bool + use<>, git2::Error> {
+        let include = (!self.include.is_empty())
+            .then(|| branch_patterns(&self.include))
+            .transpose()?;
+        let exclude = branch_patterns(&self.exclude)?;
+        Ok(move |name: &str| {
+            include.as_ref().is_none_or(|i| i.is_match(name)) && !exclude.is_match(name)
+        })
+    }
+}
+
+fn branch_patterns(patterns: &[String]) -> Result<crate::BranchPatterns, git2::Error> {
+    crate::BranchPatterns::new(patterns.iter().map(|p| p.as_str())).map_err(invalid_pattern)
+}
+
+fn invalid_pattern(err: eyre::Report) -> git2::Error {
+    git2::Error::new(
+        git2::ErrorCode::Invalid,
+        git2::ErrorClass::Config,
+        format!("invalid branch pattern: {err}"),
+    )
+}
+
+/// Customize how [`Snapshot::apply_with`] restores branches
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApplyOptions {
+    /// Delete local branches that did not exist when the snapshot was taken
+    pub prune: bool,
+    /// Branches to leave alone, as gitignore-style patterns like `release/*`
+    pub protected_branches: Vec<String>,
+    /// Restore and delete protected branches as well
+    pub force: bool,
+    /// Only restore branches with these full names or matching these globs, or all branches when
+    /// empty
+    pub branches: Vec<String>,
+    /// Overwrite branches that gained commits the snapshot never saw
+    pub allow_divergent: bool,
+    /// Before overwriting a divergent branch, save its tip as `<prefix><name>`
+    ///
+    /// Fails rather than overwrite an existing backup of a different tip.
+    pub backup_prefix: Option<String>,
+    /// Restore the remote-tracking branches recorded with each branch
+    pub remotes: bool,
+    /// Restore the tags, when the snapshot has them
+    pub tags: bool,
+    /// Point HEAD back where it was, checking out the working tree
+    pub head: bool,
+    /// Restore the `branch.<name>.*` config of branches that get created, like their upstream
+    pub branch_config: bool,
+    /// Restore what is possible when commits are missing, rather than failing
+    pub skip_missing: bool,
+    /// Restore the uncommitted changes, when the snapshot has them
+    pub worktree: bool,
+}
+
 /// State of an individual branch
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Branch {
@@ -107,11 +593,62 @@ pub struct Branch {
     #[serde(serialize_with = "serialize_oid")]
     #[serde(deserialize_with = "deserialize_oid")]
     pub id: git2::Oid,
+    /// Remote-tracking branch for `git push`, when the snapshot was taken
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub push: Option<RemoteBranch>,
+    /// Remote-tracking branch for `git pull`, when the snapshot was taken
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pull: Option<RemoteBranch>,
+    /// The branch's `branch.<name>.*` config, keyed by variable, like `remote` and `merge`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub config: std::collections::BTreeMap<String, String>,
     #[serde(default)]
     #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
     pub metadata: std::collections::BTreeMap<String, serde_json::Value>,
 }
 
+/// Where HEAD points
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Head {
+    /// Checked out branch
+    Branch(String),
+    /// Detached at a commit, like during a rebase
+    Detached(git2::Oid),
+}
+
+impl std::fmt::Display for Head {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Branch(name) => name.fmt(f),
+            Self::Detached(id) => id.fmt(f),
+        }
+    }
+}
+
+/// State of an individual tag
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Tag {
+    pub name: String,
+    /// The tag object for annotated tags, otherwise the tagged commit
+    #[serde(serialize_with = "serialize_oid")]
+    #[serde(deserialize_with = "deserialize_oid")]
+    pub id: git2::Oid,
+    #[serde(default)]
+    pub annotated: bool,
+}
+
+/// State of a remote-tracking branch, like `origin/main`
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RemoteBranch {
+    pub name: String,
+    #[serde(serialize_with = "serialize_oid")]
+    #[serde(deserialize_with = "deserialize_oid")]
+    pub id: git2::Oid,
+}
+
 fn serialize_oid<S>(id: &git2::Oid, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
@@ -129,6 +666,26 @@ where
     git2::Oid::from_str(&s).map_err(serde::de::Error::custom)
 }
 
+fn serialize_opt_oid<S>(id: &Option<git2::Oid>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match id {
+        Some(id) => serialize_oid(id, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_opt_oid<'de, D>(deserializer: D) -> Result<Option<git2::Oid>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+    let s = Option::<String>::deserialize(deserializer)?;
+    s.map(|s| git2::Oid::from_str(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
 impl PartialOrd for Branch {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -5,43 +5,84 @@ pub(crate) use super::Snapshot;
 pub struct Stack {
     pub name: String,
     root: std::path::PathBuf,
+    /// Where a linked worktree kept its own snapshots, before stacks were shared
+    legacy_root: Option<std::path::PathBuf>,
+    git_dir: std::path::PathBuf,
+    storage: Storage,
     capacity: Option<usize>,
+    retention: Option<Retention>,
+    keep_alive: bool,
 }
 
 impl Stack {
     pub const DEFAULT_STACK: &'static str = "recent";
+    /// Branches as they were before the most recent restores
+    pub const UNDO_STACK: &'static str = "undo";
     const EXT: &'static str = "bak";
+    const SNAPSHOT_FILE: &'static str = "snapshot.json";
 
     /// Create a named stack of snapshots
     pub fn new(name: &str, repo: &crate::git::GitRepo) -> Self {
-        let root = stack_root(repo.raw().path(), name);
+        // Shared by all worktrees
+        let root = stack_root(repo.raw().commondir(), name);
+        let legacy_root = (repo.raw().path() != repo.raw().commondir())
+            .then(|| stack_root(repo.raw().path(), name));
+        let git_dir = repo.raw().path().to_owned();
         let name = name.to_owned();
         Self {
             name,
             root,
+            legacy_root,
+            git_dir,
+            storage: Storage::default(),
             capacity: None,
+            retention: None,
+            keep_alive: false,
         }
     }
 
     /// Discover all stacks of snapshots
     pub fn all(repo: &crate::git::GitRepo) -> impl Iterator<Item = Self> {
-        let root = stacks_root(repo.raw().path());
-        let mut stacks: Vec<_> = std::fs::read_dir(root)
+        let mut roots = vec![stacks_root(repo.raw().commondir())];
+        if repo.raw().path() != repo.raw().commondir() {
+            roots.push(stacks_root(repo.raw().path()));
+        }
+        let mut stacks: Vec<Self> = Vec::new();
+        for root in roots {
+            let file_stacks = std::fs::read_dir(root)
+                .into_iter()
+                .flatten()
+                .filter_map(|e| {
+                    let e = e.ok()?;
+                    let e = e.file_type().ok()?.is_dir().then_some(e)?;
+                    let p = e.path();
+                    let stack_name = p.file_name()?.to_str()?.to_owned();
+                    Some(Self::new(&stack_name, repo))
+                });
+            for stack in file_stacks {
+                if !stacks.iter().any(|v| v.name == stack.name) {
+                    stacks.push(stack);
+                }
+            }
+        }
+        let ref_stacks: Vec<_> = repo
+            .raw()
+            .references_glob(&format!("{STACKS_REF}/*"))
             .into_iter()
             .flatten()
-            .filter_map(|e| {
-                let e = e.ok()?;
-                let e = e.file_type().ok()?.is_dir().then_some(e)?;
-                let p = e.path();
-                let stack_name = p.file_name()?.to_str()?.to_owned();
-                let stack_root = stack_root(repo.raw().path(), &stack_name);
-                Some(Self {
-                    name: stack_name,
-                    root: stack_root,
-                    capacity: None,
-                })
+            .filter_map(|r| {
+                let r = r.ok()?;
+                let stack_name = r.name().ok()?.strip_prefix(STACKS_REF)?.strip_prefix('/')?;
+                let mut stack = Self::new(stack_name, repo);
+                stack.storage(Storage::Refs);
+                Some(stack)
             })
             .collect();
+        for stack in ref_stacks {
+            if !stacks.iter().any(|v| v.name == stack.name) {
+                stacks.push(stack);
+            }
+        }
         if !stacks.iter().any(|v| v.name == Self::DEFAULT_STACK) {
             stacks.insert(0, Self::new(Self::DEFAULT_STACK, repo));
         }
@@ -53,27 +94,88 @@ impl Stack {
         self.capacity = capacity;
     }
 
+    /// Change how long snapshots are kept, enforced on [`Stack::push`] and [`Stack::prune`]
+    pub fn retention(&mut self, retention: Option<Retention>) {
+        self.retention = retention;
+    }
+
+    /// Change how snapshots are stored
+    pub fn storage(&mut self, storage: Storage) {
+        self.storage = storage;
+    }
+
+    /// Protect the commits of file snapshots from `git gc` with anchor refs
+    ///
+    /// [`Storage::Refs`] always keeps its commits alive.
+    pub fn keep_alive(&mut self, keep_alive: bool) {
+        self.keep_alive = keep_alive;
+    }
+
     /// Discover snapshots within this stack
-    pub fn iter(&self) -> impl DoubleEndedIterator<Item = std::path::PathBuf> {
-        let mut elements: Vec<(usize, std::path::PathBuf)> = std::fs::read_dir(&self.root)
-            .into_iter()
-            .flatten()
-            .filter_map(|e| {
-                let e = e.ok()?;
-                let e = e.file_type().ok()?.is_file().then_some(e)?;
-                let p = e.path();
-                let p = (p.extension()? == Self::EXT).then_some(p)?;
-                let index = p.file_stem()?.to_str()?.parse::<usize>().ok()?;
-                Some((index, p))
-            })
-            .collect();
-        elements.sort_unstable();
-        elements.into_iter().map(|(_, p)| p)
+    ///
+    /// Snapshots a linked worktree kept to itself, and then those left in the other [`Storage`]
+    /// from before it was changed, come first as the oldest.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = Location> {
+        let legacy = self
+            .legacy_root
+            .iter()
+            .flat_map(|root| files_in(root))
+            .map(Location::File);
+        let files = self.files().into_iter().map(Location::File);
+        let commits = self.commits().into_iter().map(Location::Commit);
+        let elements: Vec<_> = match self.storage {
+            Storage::Files => legacy.chain(commits).chain(files).collect(),
+            Storage::Refs => legacy.chain(files).chain(commits).collect(),
+        };
+        elements.into_iter()
+    }
+
+    /// Read a snapshot from this stack
+    pub fn load(&self, location: &Location) -> Result<Snapshot, std::io::Error> {
+        match location {
+            Location::File(path) => Snapshot::load(path),
+            Location::Commit(id) => {
+                let repo = self.open_repo()?;
+                let commit = repo.find_commit(*id).map_err(std::io::Error::other)?;
+                let tree = commit.tree().map_err(std::io::Error::other)?;
+                let entry = tree.get_name(Self::SNAPSHOT_FILE).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("{id} is not a snapshot"),
+                    )
+                })?;
+                let blob = repo.find_blob(entry.id()).map_err(std::io::Error::other)?;
+                let snapshot = serde_json::from_slice(blob.content())?;
+                Ok(snapshot)
+            }
+        }
     }
 
     /// Add a snapshot to this stack
-    pub fn push(&mut self, snapshot: Snapshot) -> Result<std::path::PathBuf, std::io::Error> {
-        let elems: Vec<_> = self.iter().collect();
+    pub fn push(&mut self, snapshot: Snapshot) -> Result<Location, std::io::Error> {
+        if let Some(last) = self.get(0)
+            && self
+                .load(&last)
+                .is_ok_and(|last_snapshot| last_snapshot.same_as(&snapshot))
+        {
+            log::trace!("Reusing snapshot {last}");
+            return Ok(last);
+        }
+
+        let location = match self.storage {
+            Storage::Files => self.push_file(snapshot)?,
+            Storage::Refs => self.push_commit(snapshot).map_err(std::io::Error::other)?,
+        };
+
+        if self.retention.is_some() && !self.prune().is_empty() {
+            // Pruning rewrites snapshot commits
+            return Ok(self.get(0).unwrap_or(location));
+        }
+        Ok(location)
+    }
+
+    fn push_file(&mut self, snapshot: Snapshot) -> Result<Location, std::io::Error> {
+        let elems = self.files();
         let last_path = elems.iter().last();
         let next_index = match last_path {
             Some(last_path) => {
@@ -88,25 +190,36 @@ impl Stack {
             }
             None => 0,
         };
-        let last = last_path.and_then(|p| Snapshot::load(p).ok());
-        if last.as_ref() == Some(&snapshot) {
-            let last_path = last_path.unwrap().to_owned();
-            log::trace!("Reusing snapshot {}", last_path.display());
-            return Ok(last_path);
-        }
 
         std::fs::create_dir_all(&self.root)?;
         let new_path = self.root.join(format!("{}.{}", next_index, Self::EXT));
         snapshot.save(&new_path)?;
         log::trace!("Backed up as {}", new_path.display());
 
+        // Nothing else keeps the stash commit of uncommitted changes alive
+        if self.keep_alive || snapshot.worktree.is_some() {
+            let anchored = git2::Repository::open(&self.git_dir).and_then(|repo| {
+                let anchor_id = anchor(&repo, &snapshot)?;
+                repo.reference(
+                    &self.anchor_ref_name(next_index),
+                    anchor_id,
+                    true,
+                    "branch-stash: push",
+                )?;
+                Ok(())
+            });
+            if let Err(err) = anchored {
+                log::warn!("Failed to protect snapshotted commits: {err}");
+            }
+        }
+
         if let Some(capacity) = self.capacity {
             let len = elems.len();
             if capacity < len {
                 let remove = len - capacity;
                 log::debug!("Too many snapshots, clearing {remove} oldest");
                 for snapshot_path in &elems[0..remove] {
-                    if let Err(err) = std::fs::remove_file(snapshot_path) {
+                    if let Err(err) = self.remove_file(snapshot_path) {
                         log::debug!("Failed to remove {}: {}", snapshot_path.display(), err);
                     } else {
                         log::trace!("Removed {}", snapshot_path.display());
@@ -115,28 +228,356 @@ impl Stack {
             }
         }
 
-        Ok(new_path)
+        Ok(Location::File(new_path))
+    }
+
+    fn push_commit(&mut self, snapshot: Snapshot) -> Result<Location, git2::Error> {
+        let repo = git2::Repository::open(&self.git_dir)?;
+        let elems = self.commits();
+
+        let signature = signature(&repo)?;
+        let anchor = repo.find_commit(anchor(&repo, &snapshot)?)?;
+
+        let data = serde_json::to_vec_pretty(&snapshot)
+            .map_err(|err| git2::Error::from_str(&err.to_string()))?;
+        let blob_id = repo.blob(&data)?;
+        let mut tree = repo.treebuilder(None)?;
+        tree.insert(
+            Self::SNAPSHOT_FILE,
+            blob_id,
+            i32::from(git2::FileMode::Blob),
+        )?;
+        let tree = repo.find_tree(tree.write()?)?;
+
+        let message = match snapshot.metadata.get("message") {
+            Some(serde_json::Value::String(message)) => message.clone(),
+            _ => "branch-stash: snapshot".to_owned(),
+        };
+        let last = elems.last().map(|id| repo.find_commit(*id)).transpose()?;
+        let parents: Vec<_> = last.iter().chain(std::iter::once(&anchor)).collect();
+        let new_id = repo.commit(None, &signature, &signature, &message, &tree, &parents)?;
+        self.set_ref(&repo, Some(new_id), "push")?;
+        log::trace!("Backed up as {new_id}");
+
+        if let Some(capacity) = self.capacity {
+            let len = elems.len();
+            if capacity < len {
+                let remove = len - capacity;
+                log::debug!("Too many snapshots, clearing {remove} oldest");
+                let mut kept = elems[remove..].to_vec();
+                kept.push(new_id);
+                let new_tip = rewrite(&repo, None, &kept)?;
+                self.set_ref(&repo, new_tip, "evict")?;
+                return Ok(Location::Commit(new_tip.unwrap_or(new_id)));
+            }
+        }
+
+        Ok(Location::Commit(new_id))
+    }
+
+    /// Snapshots that have aged out of the [`Retention`] policy, most recent first
+    ///
+    /// The most recent snapshot is always kept, as are snapshots of unknown age.
+    pub fn expired(&self) -> Vec<Location> {
+        let Some(retention) = self.retention else {
+            return Vec::new();
+        };
+        let now = std::time::SystemTime::now();
+        let locations: Vec<_> = self.iter().rev().collect();
+        let ages: Vec<_> = locations
+            .iter()
+            .map(|location| {
+                let timestamp = self.timestamp(location)?;
+                now.duration_since(timestamp).ok().map(|age| age.as_secs())
+            })
+            .collect();
+        locations
+            .into_iter()
+            .zip(retention.keep(&ages))
+            .filter_map(|(location, keep)| (!keep).then_some(location))
+            .collect()
+    }
+
+    /// Remove the snapshots that have aged out of the [`Retention`] policy
+    pub fn prune(&mut self) -> Vec<Location> {
+        let expired = self.expired();
+        if expired.is_empty() {
+            return expired;
+        }
+        log::debug!("Clearing {} expired snapshots", expired.len());
+        for location in &expired {
+            if let Location::File(path) = location
+                && let Err(err) = self.remove_file(path)
+            {
+                log::debug!("Failed to remove {}: {}", path.display(), err);
+            }
+        }
+        if expired.iter().any(|l| matches!(l, Location::Commit(_))) {
+            let kept: Vec<_> = self
+                .commits()
+                .into_iter()
+                .filter(|id| !expired.contains(&Location::Commit(*id)))
+                .collect();
+            let pruned = git2::Repository::open(&self.git_dir).and_then(|repo| {
+                let new_tip = rewrite(&repo, None, &kept)?;
+                self.set_ref(&repo, new_tip, "prune")
+            });
+            if let Err(err) = pruned {
+                log::debug!("Failed to prune: {err}");
+                return expired
+                    .into_iter()
+                    .filter(|l| matches!(l, Location::File(_)))
+                    .collect();
+            }
+        }
+        expired
+    }
+
+    /// When a snapshot was taken, falling back to when it was stored
+    fn timestamp(&self, location: &Location) -> Option<std::time::SystemTime> {
+        if let Some(timestamp) = self.load(location).ok().and_then(|s| s.timestamp()) {
+            return Some(timestamp);
+        }
+        match location {
+            Location::File(path) => std::fs::metadata(path).and_then(|m| m.modified()).ok(),
+            Location::Commit(id) => {
+                let repo = self.open_repo().ok()?;
+                let commit = repo.find_commit(*id).ok()?;
+                let secs = u64::try_from(commit.time().seconds()).ok()?;
+                std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_secs(secs))
+            }
+        }
     }
 
-    /// Empty the snapshot stack
+    /// Empty the snapshot stack, in either [`Storage`]
     pub fn clear(&mut self) {
         let _ = std::fs::remove_dir_all(&self.root);
+        if let Some(legacy_root) = self.legacy_root.as_deref() {
+            let _ = std::fs::remove_dir_all(legacy_root);
+        }
+        if let Ok(repo) = git2::Repository::open(&self.git_dir) {
+            let anchors = repo
+                .references_glob(&format!("{}/*", self.anchors_ref_name()))
+                .into_iter()
+                .flatten()
+                .filter_map(|r| r.ok());
+            for mut reference in anchors {
+                let _ = reference.delete();
+            }
+            let _ = self.set_ref(&repo, None, "clear");
+        }
     }
 
     /// Remove the most recent snapshot from the stack
-    pub fn pop(&mut self) -> Option<std::path::PathBuf> {
-        let mut elems: Vec<_> = self.iter().collect();
-        let last = elems.pop()?;
-        std::fs::remove_file(&last).ok()?;
-        Some(last)
+    pub fn pop(&mut self) -> Option<Location> {
+        self.remove(0)
     }
 
     /// View the most recent snapshot in the stack
-    pub fn peek(&mut self) -> Option<std::path::PathBuf> {
-        self.iter().last()
+    pub fn peek(&mut self) -> Option<Location> {
+        self.get(0)
+    }
+
+    /// Remove a snapshot from the stack, counting back from the most recent (`0`)
+    pub fn remove(&mut self, index: usize) -> Option<Location> {
+        let location = self.get(index)?;
+        match &location {
+            Location::File(path) => {
+                self.remove_file(path).ok()?;
+            }
+            Location::Commit(id) => {
+                let repo = git2::Repository::open(&self.git_dir).ok()?;
+                let elems = self.commits();
+                let position = elems.iter().position(|e| e == id)?;
+                let base = position.checked_sub(1).map(|i| elems[i]);
+                let new_tip = match rewrite(&repo, base, &elems[position + 1..]) {
+                    Ok(new_tip) => new_tip,
+                    Err(err) => {
+                        log::debug!("Failed to remove {location}: {err}");
+                        return None;
+                    }
+                };
+                self.set_ref(&repo, new_tip, "drop").ok()?;
+            }
+        }
+        Some(location)
+    }
+
+    /// View a snapshot in the stack, counting back from the most recent (`0`)
+    pub fn get(&self, index: usize) -> Option<Location> {
+        self.iter().rev().nth(index)
+    }
+
+    fn files(&self) -> Vec<std::path::PathBuf> {
+        files_in(&self.root)
+    }
+
+    fn remove_file(&self, path: &std::path::Path) -> Result<(), std::io::Error> {
+        std::fs::remove_file(path)?;
+        // Anchors are only numbered after the shared root's files
+        let index = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|_| path.parent() == Some(self.root.as_path()));
+        if let Some(index) = index
+            && let Ok(repo) = git2::Repository::open(&self.git_dir)
+            && let Ok(mut reference) = repo.find_reference(&self.anchor_ref_name(index))
+            && let Err(err) = reference.delete()
+        {
+            log::debug!("Failed to remove anchor for {}: {}", path.display(), err);
+        }
+        Ok(())
+    }
+
+    /// Snapshot commits, oldest first
+    fn commits(&self) -> Vec<git2::Oid> {
+        let Ok(repo) = git2::Repository::open(&self.git_dir) else {
+            return Vec::new();
+        };
+        let mut next = repo
+            .find_reference(&self.ref_name())
+            .ok()
+            .and_then(|r| r.target());
+        let mut elements = Vec::new();
+        while let Some(id) = next {
+            elements.push(id);
+            next = repo
+                .find_commit(id)
+                .ok()
+                .filter(|c| c.parent_count() == 2)
+                .and_then(|c| c.parent_id(0).ok());
+        }
+        elements.reverse();
+        elements
+    }
+
+    fn ref_name(&self) -> String {
+        format!("{STACKS_REF}/{}", self.name)
+    }
+
+    fn anchors_ref_name(&self) -> String {
+        format!("{ANCHORS_REF}/{}", self.name)
+    }
+
+    fn anchor_ref_name(&self, index: usize) -> String {
+        format!("{}/{index}", self.anchors_ref_name())
+    }
+
+    fn set_ref(
+        &self,
+        repo: &git2::Repository,
+        id: Option<git2::Oid>,
+        action: &str,
+    ) -> Result<(), git2::Error> {
+        let ref_name = self.ref_name();
+        match id {
+            Some(id) => {
+                repo.reference_ensure_log(&ref_name)?;
+                repo.reference(&ref_name, id, true, &format!("branch-stash: {action}"))?;
+            }
+            None => {
+                if let Ok(mut reference) = repo.find_reference(&ref_name) {
+                    reference.delete()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn open_repo(&self) -> Result<git2::Repository, std::io::Error> {
+        git2::Repository::open(&self.git_dir).map_err(std::io::Error::other)
     }
 }
 
+/// How a [`Stack`] persists its snapshots
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Storage {
+    /// JSON files in `.git/branch-stash/<stack>/`
+    #[default]
+    Files,
+    /// Commits under `refs/branch-stash/<stack>`, keeping the snapshotted commits alive
+    Refs,
+}
+
+impl std::str::FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "files" => Ok(Self::Files),
+            "refs" => Ok(Self::Refs),
+            _ => Err(format!("unknown storage `{s}`, expected `files` or `refs`")),
+        }
+    }
+}
+
+impl std::fmt::Display for Storage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Files => "files".fmt(f),
+            Self::Refs => "refs".fmt(f),
+        }
+    }
+}
+
+/// How long a [`Stack`] keeps its snapshots
+///
+/// A snapshot is kept if any of the rules keep it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// Keep every snapshot from the last this many hours
+    pub hours: u64,
+    /// Keep the most recent snapshot of each day, for this many days
+    pub days: u64,
+    /// Keep the most recent snapshot of each week, for this many weeks
+    pub weeks: u64,
+}
+
+impl Retention {
+    /// Which snapshots to keep, given their ages in seconds, most recent first
+    fn keep(&self, ages: &[Option<u64>]) -> Vec<bool> {
+        let mut days = std::collections::BTreeSet::new();
+        let mut weeks = std::collections::BTreeSet::new();
+        ages.iter()
+            .enumerate()
+            .map(|(index, age)| {
+                let Some(age) = *age else {
+                    return true;
+                };
+                let recent = age < self.hours.saturating_mul(HOUR);
+                let daily = age / DAY < self.days && days.insert(age / DAY);
+                let weekly = age / WEEK < self.weeks && weeks.insert(age / WEEK);
+                // Checked last so the most recent snapshot still claims its day and week
+                recent || daily || weekly || index == 0
+            })
+            .collect()
+    }
+}
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// Where a snapshot lives within its [`Stack`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Location {
+    File(std::path::PathBuf),
+    Commit(git2::Oid),
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(path) => path.display().fmt(f),
+            Self::Commit(id) => id.fmt(f),
+        }
+    }
+}
+
+const STACKS_REF: &str = "refs/branch-stash";
+const ANCHORS_REF: &str = "refs/branch-stash-anchors";
+
 fn stacks_root(repo: &std::path::Path) -> std::path::PathBuf {
     repo.join("branch-stash")
 }
@@ -144,3 +585,133 @@ fn stacks_root(repo: &std::path::Path) -> std::path::PathBuf {
 fn stack_root(repo: &std::path::Path, stack: &str) -> std::path::PathBuf {
     repo.join("branch-stash").join(stack)
 }
+
+/// Snapshot files in `root`, oldest first
+fn files_in(root: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut elements: Vec<(usize, std::path::PathBuf)> = std::fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| {
+            let e = e.ok()?;
+            let e = e.file_type().ok()?.is_file().then_some(e)?;
+            let p = e.path();
+            let p = (p.extension()? == Stack::EXT).then_some(p)?;
+            let index = p.file_stem()?.to_str()?.parse::<usize>().ok()?;
+            Some((index, p))
+        })
+        .collect();
+    elements.sort_unstable();
+    elements.into_iter().map(|(_, p)| p).collect()
+}
+
+fn signature(repo: &git2::Repository) -> Result<git2::Signature<'static>, git2::Error> {
+    repo.signature()
+        .or_else(|_| git2::Signature::now("git-branch-stash", "git-branch-stash@localhost"))
+}
+
+/// Create a commit whose parents are every distinct commit in the snapshot
+fn anchor(repo: &git2::Repository, snapshot: &Snapshot) -> Result<git2::Oid, git2::Error> {
+    let signature = signature(repo)?;
+    // Tag objects can't be parents, so only the commits they point to are kept alive
+    let tags = snapshot.tags.iter().flatten().filter_map(|t| {
+        repo.find_object(t.id, None)
+            .and_then(|o| o.peel_to_commit())
+            .map(|c| c.id())
+            .ok()
+    });
+    let tips: Vec<_> = snapshot
+        .branches
+        .iter()
+        .map(|b| b.id)
+        .chain(tags)
+        .chain(snapshot.worktree)
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|id| repo.find_commit(id).ok())
+        .collect();
+    let tips: Vec<_> = tips.iter().collect();
+    let empty_tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+    repo.commit(
+        None,
+        &signature,
+        &signature,
+        "branch-stash: snapshotted commits",
+        &empty_tree,
+        &tips,
+    )
+}
+
+/// Re-parent snapshot commits on top of `base`, returning the new tip
+///
+/// Snapshot commits have their anchor as the last parent, preceded by the previous snapshot, if
+/// any.
+fn rewrite(
+    repo: &git2::Repository,
+    base: Option<git2::Oid>,
+    commits: &[git2::Oid],
+) -> Result<Option<git2::Oid>, git2::Error> {
+    let mut tip = base;
+    for id in commits {
+        let commit = repo.find_commit(*id)?;
+        let anchor = commit.parent(commit.parent_count().saturating_sub(1))?;
+        let prev = tip.map(|id| repo.find_commit(id)).transpose()?;
+        let parents: Vec<_> = prev.iter().chain(std::iter::once(&anchor)).collect();
+        let new_id = repo.commit(
+            None,
+            &commit.author(),
+            &commit.committer(),
+            commit.message_raw().unwrap_or_default(),
+            &commit.tree()?,
+            &parents,
+        )?;
+        tip = Some(new_id);
+    }
+    Ok(tip)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn retention_keep() {
+        let retention = Retention {
+            hours: 24,
+            days: 7,
+            weeks: 4,
+        };
+        let ages = [
+            Some(2 * DAY + 10),
+            Some(2 * DAY + 20),
+            Some(3 * DAY),
+            Some(10 * DAY),
+            Some(12 * DAY),
+            None,
+            Some(40 * DAY),
+        ];
+        assert_eq!(
+            retention.keep(&ages),
+            [true, false, true, true, false, true, false]
+        );
+
+        // The most recent snapshot is always kept, and still claims its day
+        let retention = Retention {
+            hours: 0,
+            days: 7,
+            weeks: 0,
+        };
+        let ages = [Some(HOUR), Some(2 * HOUR), Some(DAY + HOUR), Some(8 * DAY)];
+        assert_eq!(retention.keep(&ages), [true, false, true, false]);
+        assert_eq!(
+            Retention::default().keep(&ages),
+            [true, false, false, false]
+        );
+
+        let retention = Retention {
+            hours: u64::MAX,
+            days: u64::MAX,
+            weeks: u64::MAX,
+        };
+        assert_eq!(retention.keep(&ages), [true, true, true, true]);
+    }
+}
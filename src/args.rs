@@ -26,14 +26,26 @@ pub(crate) enum Subcommand {
     List(ListArgs),
     /// Clear all snapshots
     Clear(ClearArgs),
-    /// Delete the last snapshot
+    /// Delete a snapshot, defaulting to the last
     Drop(DropArgs),
-    /// Apply the last snapshot, deleting it
+    /// Delete the snapshots that are past the retention policy
+    Prune(PruneArgs),
+    /// Apply a snapshot, deleting it, defaulting to the last
     Pop(ApplyArgs),
-    /// Apply the last snapshot
+    /// Apply a snapshot, defaulting to the last
     Apply(ApplyArgs),
+    /// Show the details of a snapshot, defaulting to the last
+    Show(ShowArgs),
+    /// Show how applying a snapshot would change the branches, defaulting to the last
+    Diff(DiffArgs),
     /// List all snapshot stacks
     Stacks(StacksArgs),
+    /// Check that all snapshots can be restored
+    Verify(VerifyArgs),
+    /// Restore the branches from before the last apply or pop
+    Undo(UndoArgs),
+    /// Show or change the config, defaulting to showing it
+    Config(ConfigArgs),
 }
 
 #[derive(Debug, clap::Args)]
@@ -45,6 +57,22 @@ pub(crate) struct PushArgs {
     /// Annotate the snapshot with the given message
     #[arg(short, long)]
     pub(crate) message: Option<String>,
+
+    /// Only stash branches matching the glob, overriding `branch-stash.include`
+    #[arg(long, value_name = "GLOB")]
+    pub(crate) include: Vec<String>,
+
+    /// Don't stash branches matching the glob, overriding `branch-stash.exclude`
+    #[arg(long, value_name = "GLOB")]
+    pub(crate) exclude: Vec<String>,
+
+    /// Stash tags as well
+    #[arg(long)]
+    pub(crate) tags: bool,
+
+    /// Capture uncommitted changes to tracked files as well
+    #[arg(long)]
+    pub(crate) include_worktree: bool,
 }
 
 #[derive(Debug, clap::Args)]
@@ -62,22 +90,246 @@ pub(crate) struct ClearArgs {
 }
 
 #[derive(Debug, clap::Args)]
-pub(crate) struct DropArgs {
+pub(crate) struct PruneArgs {
     /// Specify which stash stack to use
     #[arg(default_value = git_branch_stash::Stack::DEFAULT_STACK)]
     pub(crate) stack: String,
+
+    /// Show which snapshots would be deleted
+    #[arg(short = 'n', long)]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct DropArgs {
+    #[command(flatten)]
+    pub(crate) snapshot: SnapshotArgs,
 }
 
 #[derive(Debug, clap::Args)]
 pub(crate) struct ApplyArgs {
-    /// Specify which stash stack to use
-    #[arg(default_value = git_branch_stash::Stack::DEFAULT_STACK)]
-    pub(crate) stack: String,
+    #[command(flatten)]
+    pub(crate) snapshot: SnapshotArgs,
+
+    /// Delete local branches that are not in the snapshot
+    #[arg(long)]
+    pub(crate) prune: bool,
+
+    /// Restore the branches whose commits still exist, skipping the rest
+    #[arg(long)]
+    pub(crate) skip_missing: bool,
+
+    /// Restore protected branches too and overwrite branches with commits not in the snapshot
+    #[arg(short, long)]
+    pub(crate) force: bool,
+
+    /// Save the tips of branches with commits not in the snapshot as `<PREFIX><name>`
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "backup/"
+    )]
+    pub(crate) backup: Option<String>,
+
+    /// Also restore remote-tracking branches, like after a bad `git fetch --prune`
+    #[arg(long)]
+    pub(crate) remotes: bool,
+
+    /// Also restore where HEAD pointed, including a detached HEAD
+    #[arg(long)]
+    pub(crate) head: bool,
+
+    /// Also restore uncommitted changes, if the snapshot has them, onto a clean working tree
+    #[arg(long)]
+    pub(crate) worktree: bool,
+
+    /// Also restore tags, if the snapshot has them
+    #[arg(long)]
+    pub(crate) tags: bool,
+
+    /// Restore the upstream and other `branch.<name>.*` config of recreated branches
+    #[arg(long)]
+    pub(crate) branch_config: bool,
+
+    /// Only restore the branch with this full name, or matching this glob
+    #[arg(short, long, value_name = "GLOB")]
+    pub(crate) branch: Vec<String>,
+
+    /// Create branches named `<PREFIX><name>` rather than moving existing ones, like `restored/`
+    #[arg(long)]
+    pub(crate) prefix: Option<String>,
+
+    /// Create branches named `<name><SUFFIX>` rather than moving existing ones
+    #[arg(long)]
+    pub(crate) suffix: Option<String>,
+
+    /// When popping, delete the snapshot even if only part of it was restored
+    #[arg(long)]
+    pub(crate) drop: bool,
+
+    /// Show what would change without modifying the repo or stack
+    #[arg(short = 'n', long)]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct ShowArgs {
+    #[command(flatten)]
+    pub(crate) snapshot: SnapshotArgs,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct DiffArgs {
+    #[command(flatten)]
+    pub(crate) snapshot: SnapshotArgs,
+
+    /// Compare against another snapshot (`<stack>@{<index>}`) instead of the current branches
+    #[arg(long, value_name = "SNAPSHOT")]
+    pub(crate) base: Option<SnapshotRef>,
+
+    /// Exit with 1 if there are differences and 0 if there are none
+    #[arg(long)]
+    pub(crate) exit_code: bool,
+
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub(crate) format: Format,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Format {
+    Text,
+    Json,
 }
 
 #[derive(Debug, clap::Args)]
 pub(crate) struct StacksArgs {}
 
+#[derive(Debug, clap::Args)]
+pub(crate) struct VerifyArgs {}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct ConfigArgs {
+    #[command(subcommand)]
+    pub(crate) action: Option<ConfigAction>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum ConfigAction {
+    /// Show the effective config
+    Show,
+    /// List the local branches that are protected
+    Protected,
+    /// Protect branches matching a glob
+    Protect(ProtectArgs),
+    /// Stop protecting branches matching a glob
+    Unprotect(ProtectArgs),
+    /// Set how many snapshots to keep per stack, 0 for no limit
+    Capacity(CapacityArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct ProtectArgs {
+    /// Branch name or glob, like `release/*`
+    pub(crate) glob: String,
+
+    #[command(flatten)]
+    pub(crate) target: ConfigTarget,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct CapacityArgs {
+    pub(crate) capacity: usize,
+
+    #[command(flatten)]
+    pub(crate) target: ConfigTarget,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct ConfigTarget {
+    /// Write to `$REPO/.gitconfig`, to commit and share with your team, rather than `$REPO/.git/config`
+    #[arg(long)]
+    pub(crate) shared: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct UndoArgs {
+    /// Restore protected branches too
+    #[arg(short, long)]
+    pub(crate) force: bool,
+
+    /// Show what would change without modifying the repo or stack
+    #[arg(short = 'n', long)]
+    pub(crate) dry_run: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct SnapshotArgs {
+    /// Specify which stash stack to use, optionally selecting a snapshot with `<stack>@{<index>}`
+    #[arg(default_value = git_branch_stash::Stack::DEFAULT_STACK)]
+    pub(crate) stack: SnapshotRef,
+
+    /// Specify which snapshot to use, counting back from the most recent (`0`)
+    pub(crate) index: Option<usize>,
+}
+
+impl SnapshotArgs {
+    pub(crate) fn stack(&self) -> &str {
+        &self.stack.stack
+    }
+
+    pub(crate) fn index(&self) -> Result<usize, String> {
+        match (self.stack.index, self.index) {
+            (Some(_), Some(_)) => Err(format!(
+                "snapshot index given both in `{}` and as an argument",
+                self.stack
+            )),
+            (Some(index), None) | (None, Some(index)) => Ok(index),
+            (None, None) => Ok(0),
+        }
+    }
+}
+
+/// A stack name with an optional snapshot index, like `recent@{2}`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SnapshotRef {
+    pub(crate) stack: String,
+    pub(crate) index: Option<usize>,
+}
+
+impl std::str::FromStr for SnapshotRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once("@{") {
+            Some((stack, index)) => {
+                let index = index
+                    .strip_suffix('}')
+                    .and_then(|i| i.parse::<usize>().ok())
+                    .ok_or_else(|| format!("expected `<stack>@{{<index>}}`, got `{s}`"))?;
+                Ok(Self {
+                    stack: stack.to_owned(),
+                    index: Some(index),
+                })
+            }
+            None => Ok(Self {
+                stack: s.to_owned(),
+                index: None,
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for SnapshotRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}@{{{}}}", self.stack, index),
+            None => write!(f, "{}", self.stack),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -87,4 +339,18 @@ mod test {
         use clap::CommandFactory;
         Args::command().debug_assert();
     }
+
+    #[test]
+    fn parse_snapshot_ref() {
+        let stack: SnapshotRef = "recent".parse().unwrap();
+        assert_eq!(stack.stack, "recent");
+        assert_eq!(stack.index, None);
+
+        let indexed: SnapshotRef = "recent@{2}".parse().unwrap();
+        assert_eq!(indexed.stack, "recent");
+        assert_eq!(indexed.index, Some(2));
+        assert_eq!(indexed.to_string(), "recent@{2}");
+
+        assert!("recent@{two}".parse::<SnapshotRef>().is_err());
+    }
 }
@@ -34,29 +34,57 @@ fn run() -> proc_exit::ExitResult {
         args::Subcommand::List(sub_args) => list(sub_args),
         args::Subcommand::Clear(sub_args) => clear(sub_args),
         args::Subcommand::Drop(sub_args) => drop(sub_args),
+        args::Subcommand::Prune(sub_args) => prune(sub_args),
         args::Subcommand::Pop(sub_args) => apply(sub_args, true),
         args::Subcommand::Apply(sub_args) => apply(sub_args, false),
+        args::Subcommand::Show(sub_args) => show(sub_args),
+        args::Subcommand::Diff(sub_args) => diff(sub_args),
         args::Subcommand::Stacks(sub_args) => stacks(sub_args),
+        args::Subcommand::Verify(sub_args) => verify(sub_args),
+        args::Subcommand::Undo(sub_args) => undo(sub_args),
+        args::Subcommand::Config(sub_args) => config(sub_args),
     }
 }
 
 fn push(args: args::PushArgs) -> proc_exit::ExitResult {
     let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
     let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
-    let repo = git_branch_stash::GitRepo::new(repo);
-    let mut stack = git_branch_stash::Stack::new(&args.stack, &repo);
-
+    let mut repo = git_branch_stash::GitRepo::new(repo);
     let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
         .with_code(proc_exit::Code::FAILURE)?;
+    set_remotes(&mut repo, &repo_config);
+    let mut stack = open_stack(&args.stack, &repo, &repo_config);
 
-    stack.capacity(repo_config.capacity());
-
-    if is_dirty(&repo) {
+    let worktree = if !is_dirty(&repo) {
+        None
+    } else if args.include_worktree {
+        let stash_id = stash_push(&mut repo, "branch-stash");
+        stash_pop(&mut repo, stash_id);
+        if stash_id.is_none() {
+            log::warn!("Failed to capture uncommitted changes, only capturing committed changes");
+        }
+        stash_id
+    } else {
         log::warn!("Working tree is dirty, only capturing committed changes");
-    }
+        None
+    };
 
-    let mut snapshot =
-        git_branch_stash::Snapshot::from_repo(&repo).with_code(proc_exit::Code::FAILURE)?;
+    let options = git_branch_stash::SnapshotOptions {
+        include: if args.include.is_empty() {
+            repo_config.include().to_vec()
+        } else {
+            args.include
+        },
+        exclude: if args.exclude.is_empty() {
+            repo_config.exclude().to_vec()
+        } else {
+            args.exclude
+        },
+        tags: args.tags,
+    };
+    let mut snapshot = git_branch_stash::Snapshot::from_repo_with(&repo, &options)
+        .with_code(proc_exit::Code::FAILURE)?;
+    snapshot.worktree = worktree;
     if let Some(message) = args.message.as_deref() {
         snapshot.insert_message(message);
     }
@@ -71,65 +99,359 @@ fn list(args: args::ListArgs) -> proc_exit::ExitResult {
     let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
     let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
     let repo = git_branch_stash::GitRepo::new(repo);
-    let stack = git_branch_stash::Stack::new(&args.stack, &repo);
+    let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+        .with_code(proc_exit::Code::FAILURE)?;
+    let stack = open_stack(&args.stack, &repo, &repo_config);
 
     let snapshots: Vec<_> = stack.iter().collect();
     let mut stdout = stdout().lock();
-    for (i, snapshot_path) in snapshots.iter().enumerate() {
+    for (i, location) in snapshots.iter().enumerate() {
         let style = if i < snapshots.len() - 1 {
             palette.info
         } else {
             palette.good
         };
-        let snapshot = match git_branch_stash::Snapshot::load(snapshot_path) {
+        let snapshot = match stack.load(location) {
             Ok(snapshot) => snapshot,
             Err(err) => {
-                log::error!(
-                    "Failed to load snapshot {}: {}",
-                    snapshot_path.display(),
-                    err
-                );
+                log::error!("Failed to load snapshot {location}: {err}");
                 continue;
             }
         };
-        match snapshot.metadata.get("message") {
-            Some(message) => {
+        let index = snapshots.len() - 1 - i;
+        write_snapshot(&mut stdout, &stack.name, index, location, &snapshot, style)?;
+        writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+    }
+
+    Ok(())
+}
+
+fn show(args: args::ShowArgs) -> proc_exit::ExitResult {
+    let palette = Palette::colored();
+
+    let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
+    let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
+    let repo = git_branch_stash::GitRepo::new(repo);
+    let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+        .with_code(proc_exit::Code::FAILURE)?;
+    let stack = open_stack(args.snapshot.stack(), &repo, &repo_config);
+    let index = args
+        .snapshot
+        .index()
+        .map_err(|err| proc_exit::bash::USAGE.with_message(err))?;
+
+    let (location, snapshot) = load_snapshot(&stack, index)?;
+
+    let mut stdout = stdout().lock();
+    writeln!(
+        stdout,
+        "{:#}",
+        Styled::new(format_args!("{}@{{{}}}", stack.name, index), palette.good)
+    )
+    .with_code(proc_exit::Code::FAILURE)?;
+    writeln!(stdout, "Location: {location}").with_code(proc_exit::Code::FAILURE)?;
+    if let Some(timestamp) = snapshot.timestamp() {
+        writeln!(
+            stdout,
+            "Date: {}",
+            humantime::format_rfc3339_seconds(timestamp)
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
+    }
+    for (key, value) in snapshot.metadata.iter().filter(|(k, _)| *k != "timestamp") {
+        writeln!(stdout, "{key}: {value}").with_code(proc_exit::Code::FAILURE)?;
+    }
+
+    for branch in snapshot.branches.iter() {
+        writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+        writeln!(
+            stdout,
+            "{:#}",
+            Styled::new(format_args!("branch {}", branch.name), palette.info)
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
+        match repo.find_commit(branch.id) {
+            Some(commit) => {
+                writeln!(stdout, "Commit: {}", branch.id).with_code(proc_exit::Code::FAILURE)?;
+                writeln!(
+                    stdout,
+                    "Author: {}",
+                    commit.author.as_deref().unwrap_or("<unknown>")
+                )
+                .with_code(proc_exit::Code::FAILURE)?;
                 writeln!(
                     stdout,
-                    "{}",
-                    Styled::new(format_args!("Message: {message}"), style)
+                    "Date:   {}",
+                    humantime::format_rfc3339_seconds(commit.time)
                 )
                 .with_code(proc_exit::Code::FAILURE)?;
+                writeln!(stdout, "Summary: {}", commit.summary)
+                    .with_code(proc_exit::Code::FAILURE)?;
             }
             None => {
                 writeln!(
                     stdout,
-                    "{}",
-                    Styled::new(format_args!("Path: {}", snapshot_path.display()), style)
+                    "Commit: {} {:#}",
+                    branch.id,
+                    Styled::new("(missing, cannot be restored)", palette.error)
                 )
                 .with_code(proc_exit::Code::FAILURE)?;
+                if let Some(serde_json::Value::String(summary)) = branch.metadata.get("summary") {
+                    writeln!(stdout, "Summary: {summary}").with_code(proc_exit::Code::FAILURE)?;
+                }
             }
         }
-        for branch in snapshot.branches.iter() {
-            let summary = if let Some(summary) = branch.metadata.get("summary") {
-                summary.to_string()
-            } else {
-                branch.id.to_string()
-            };
-            let name =
-                if let Some(serde_json::Value::String(parent)) = branch.metadata.get("parent") {
-                    format!("{}..{}", parent, branch.name)
-                } else {
-                    branch.name.clone()
-                };
+        if let Some(push) = branch.push.as_ref() {
+            write_remote_branch(&mut stdout, "Push:  ", push, branch.id, palette)?;
+        }
+        if let Some(pull) = branch
+            .pull
+            .as_ref()
+            .filter(|p| Some(*p) != branch.push.as_ref())
+        {
+            write_remote_branch(&mut stdout, "Pull:  ", pull, branch.id, palette)?;
+        }
+        for (var, value) in branch.config.iter() {
+            writeln!(stdout, "Config: {var}={value}").with_code(proc_exit::Code::FAILURE)?;
+        }
+        for (key, value) in branch.metadata.iter().filter(|(k, _)| *k != "summary") {
+            writeln!(stdout, "{key}: {value}").with_code(proc_exit::Code::FAILURE)?;
+        }
+    }
+
+    for tag in snapshot.tags.iter().flatten() {
+        writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+        writeln!(
+            stdout,
+            "{:#}",
+            Styled::new(format_args!("tag {}", tag.name), palette.info)
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
+        let kind = if tag.annotated { "Object" } else { "Commit" };
+        writeln!(stdout, "{kind}: {}", tag.id).with_code(proc_exit::Code::FAILURE)?;
+    }
+
+    if let Some(worktree) = snapshot.worktree {
+        writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+        writeln!(
+            stdout,
+            "{:#}",
+            Styled::new("uncommitted changes", palette.info)
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
+        writeln!(stdout, "Stash: {worktree}").with_code(proc_exit::Code::FAILURE)?;
+    }
+
+    Ok(())
+}
+
+fn write_remote_branch(
+    stdout: &mut impl Write,
+    label: &str,
+    remote: &git_branch_stash::RemoteBranch,
+    id: git2::Oid,
+    palette: Palette,
+) -> proc_exit::ExitResult {
+    write!(stdout, "{label} {} {}", remote.name, remote.id).with_code(proc_exit::Code::FAILURE)?;
+    if remote.id == id {
+        write!(stdout, " {:#}", Styled::new("(up to date)", palette.hint))
+            .with_code(proc_exit::Code::FAILURE)?;
+    }
+    writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+    Ok(())
+}
+
+fn diff(args: args::DiffArgs) -> proc_exit::ExitResult {
+    let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
+    let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
+    let mut repo = git_branch_stash::GitRepo::new(repo);
+    let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+        .with_code(proc_exit::Code::FAILURE)?;
+    set_remotes(&mut repo, &repo_config);
+    let stack = open_stack(args.snapshot.stack(), &repo, &repo_config);
+    let index = args
+        .snapshot
+        .index()
+        .map_err(|err| proc_exit::bash::USAGE.with_message(err))?;
+
+    let (_, snapshot) = load_snapshot(&stack, index)?;
+
+    let base = match args.base.as_ref() {
+        Some(base) => {
+            let base_stack = open_stack(&base.stack, &repo, &repo_config);
+            let (_, base) = load_snapshot(&base_stack, base.index.unwrap_or(0))?;
+            base
+        }
+        None => git_branch_stash::Snapshot::from_repo_with(&repo, &snapshot.options())
+            .with_code(proc_exit::Code::FAILURE)?,
+    };
+
+    let mut diff = base.diff(&snapshot);
+    diff.count_commits(&repo);
+    match args.format {
+        args::Format::Text => show_diff(&diff)?,
+        args::Format::Json => {
+            let mut stdout = stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &diff).with_code(proc_exit::Code::FAILURE)?;
+            writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+        }
+    }
+
+    if args.exit_code && !diff.is_empty() {
+        proc_exit::Code::FAILURE.ok()
+    } else {
+        Ok(())
+    }
+}
+
+fn set_remotes(
+    repo: &mut git_branch_stash::GitRepo,
+    repo_config: &git_branch_stash::config::RepoConfig,
+) {
+    if let Some(remote) = repo_config.push_remote() {
+        repo.set_push_remote(remote);
+    }
+    if let Some(remote) = repo_config.pull_remote() {
+        repo.set_pull_remote(remote);
+    }
+}
+
+fn open_stack(
+    name: &str,
+    repo: &git_branch_stash::GitRepo,
+    repo_config: &git_branch_stash::config::RepoConfig,
+) -> git_branch_stash::Stack {
+    let mut stack = git_branch_stash::Stack::new(name, repo);
+    stack.capacity(repo_config.capacity());
+    stack.retention(repo_config.retention());
+    stack.storage(repo_config.storage());
+    stack.keep_alive(repo_config.keep_alive());
+    stack
+}
+
+fn load_snapshot(
+    stack: &git_branch_stash::Stack,
+    index: usize,
+) -> Result<(git_branch_stash::Location, git_branch_stash::Snapshot), proc_exit::Exit> {
+    let Some(location) = stack.get(index) else {
+        return Err(proc_exit::bash::USAGE
+            .with_message(format!("No snapshot at {}@{{{}}}", stack.name, index)));
+    };
+    let snapshot = stack.load(&location).with_code(proc_exit::Code::FAILURE)?;
+    Ok((location, snapshot))
+}
+
+fn show_diff(diff: &git_branch_stash::Diff) -> proc_exit::ExitResult {
+    let palette = Palette::colored();
+
+    let mut stdout = stdout().lock();
+    for branch in diff.branches.iter() {
+        let old_id = branch.old_id.unwrap_or(git2::Oid::ZERO_SHA1);
+        let new_id = branch.new_id.unwrap_or(git2::Oid::ZERO_SHA1);
+        match branch.status() {
+            git_branch_stash::DiffStatus::Added => {
+                write!(
+                    stdout,
+                    "{:#}",
+                    Styled::new(format_args!("+ {}: {new_id}", branch.name), palette.good)
+                )
+                .with_code(proc_exit::Code::FAILURE)?;
+            }
+            git_branch_stash::DiffStatus::Removed => {
+                write!(
+                    stdout,
+                    "{:#}",
+                    Styled::new(format_args!("- {}: {old_id}", branch.name), palette.error)
+                )
+                .with_code(proc_exit::Code::FAILURE)?;
+            }
+            git_branch_stash::DiffStatus::Moved => {
+                write!(
+                    stdout,
+                    "{:#}",
+                    Styled::new(
+                        format_args!("~ {}: {old_id} -> {new_id}", branch.name),
+                        palette.warn
+                    )
+                )
+                .with_code(proc_exit::Code::FAILURE)?;
+                if let (Some(ahead), Some(behind)) = (branch.ahead, branch.behind) {
+                    write!(
+                        stdout,
+                        " {:#}",
+                        Styled::new(format_args!("(+{ahead} -{behind})"), palette.hint)
+                    )
+                    .with_code(proc_exit::Code::FAILURE)?;
+                }
+            }
+        }
+        match branch.new_push.as_ref() {
+            Some(push) if Some(push.id) == branch.new_id => {
+                write!(stdout, " {:#}", Styled::new("(pushed)", palette.hint))
+            }
+            Some(push) => write!(
+                stdout,
+                " {:#}",
+                Styled::new(format_args!("({} at {})", push.name, push.id), palette.hint)
+            ),
+            None => Ok(()),
+        }
+        .with_code(proc_exit::Code::FAILURE)?;
+        writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+    }
+
+    Ok(())
+}
+
+fn write_snapshot(
+    stdout: &mut impl Write,
+    stack: &str,
+    index: usize,
+    location: &git_branch_stash::Location,
+    snapshot: &git_branch_stash::Snapshot,
+    style: anstyle::Style,
+) -> proc_exit::ExitResult {
+    match snapshot.metadata.get("message") {
+        Some(message) => {
             writeln!(
                 stdout,
                 "{}",
-                Styled::new(format_args!("- {name}: {summary}"), style),
+                Styled::new(
+                    format_args!("{stack}@{{{index}}}: Message: {message}"),
+                    style
+                )
             )
             .with_code(proc_exit::Code::FAILURE)?;
         }
-        writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+        None => {
+            writeln!(
+                stdout,
+                "{}",
+                Styled::new(
+                    format_args!("{stack}@{{{index}}}: Location: {location}"),
+                    style
+                )
+            )
+            .with_code(proc_exit::Code::FAILURE)?;
+        }
+    }
+    for branch in snapshot.branches.iter() {
+        let summary = if let Some(summary) = branch.metadata.get("summary") {
+            summary.to_string()
+        } else {
+            branch.id.to_string()
+        };
+        let name = if let Some(serde_json::Value::String(parent)) = branch.metadata.get("parent") {
+            format!("{}..{}", parent, branch.name)
+        } else {
+            branch.name.clone()
+        };
+        writeln!(
+            stdout,
+            "{}",
+            Styled::new(format_args!("- {name}: {summary}"), style),
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
     }
 
     Ok(())
@@ -161,20 +483,62 @@ fn clear(args: args::ClearArgs) -> proc_exit::ExitResult {
     let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
     let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
     let repo = git_branch_stash::GitRepo::new(repo);
-    let mut stack = git_branch_stash::Stack::new(&args.stack, &repo);
+    let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+        .with_code(proc_exit::Code::FAILURE)?;
+    let mut stack = open_stack(&args.stack, &repo, &repo_config);
 
     stack.clear();
 
     Ok(())
 }
 
+fn prune(args: args::PruneArgs) -> proc_exit::ExitResult {
+    let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
+    let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
+    let repo = git_branch_stash::GitRepo::new(repo);
+    let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+        .with_code(proc_exit::Code::FAILURE)?;
+    let mut stack = open_stack(&args.stack, &repo, &repo_config);
+
+    if repo_config.retention().is_none() {
+        log::warn!("No retention policy configured (see `branch-stash.keep-hours`)");
+        return Ok(());
+    }
+
+    let expired = if args.dry_run {
+        stack.expired()
+    } else {
+        stack.prune()
+    };
+    if expired.is_empty() {
+        log::info!("Nothing to prune");
+    }
+    for location in expired {
+        if args.dry_run {
+            log::info!("Would remove {location}");
+        } else {
+            log::info!("Removed {location}");
+        }
+    }
+
+    Ok(())
+}
+
 fn drop(args: args::DropArgs) -> proc_exit::ExitResult {
     let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
     let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
     let repo = git_branch_stash::GitRepo::new(repo);
-    let mut stack = git_branch_stash::Stack::new(&args.stack, &repo);
+    let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+        .with_code(proc_exit::Code::FAILURE)?;
+    let mut stack = open_stack(args.snapshot.stack(), &repo, &repo_config);
+    let index = args
+        .snapshot
+        .index()
+        .map_err(|err| proc_exit::bash::USAGE.with_message(err))?;
 
-    stack.pop();
+    if stack.remove(index).is_none() {
+        log::warn!("Nothing to drop");
+    }
 
     Ok(())
 }
@@ -183,26 +547,91 @@ fn apply(args: args::ApplyArgs, pop: bool) -> proc_exit::ExitResult {
     let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
     let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
     let mut repo = git_branch_stash::GitRepo::new(repo);
-    let mut stack = git_branch_stash::Stack::new(&args.stack, &repo);
+    let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+        .with_code(proc_exit::Code::FAILURE)?;
+    set_remotes(&mut repo, &repo_config);
+    let mut stack = open_stack(args.snapshot.stack(), &repo, &repo_config);
+    let index = args
+        .snapshot
+        .index()
+        .map_err(|err| proc_exit::bash::USAGE.with_message(err))?;
 
-    match stack.peek() {
-        Some(last) => {
-            let snapshot =
-                git_branch_stash::Snapshot::load(&last).with_code(proc_exit::Code::FAILURE)?;
+    let rename = args.prefix.is_some() || args.suffix.is_some();
+    let options = git_branch_stash::ApplyOptions {
+        prune: args.prune,
+        protected_branches: repo_config.protected_branches().to_vec(),
+        skip_missing: args.skip_missing,
+        // Renaming leaves the protected branches alone
+        force: args.force || rename,
+        branches: args.branch,
+        allow_divergent: args.force || rename,
+        backup_prefix: args.backup.filter(|_| !rename),
+        remotes: args.remotes && !rename,
+        tags: args.tags,
+        head: args.head && !rename,
+        branch_config: args.branch_config,
+        worktree: args.worktree,
+    };
 
-            let stash_id = stash_push(&mut repo, "branch-stash");
-            if is_dirty(&repo) {
-                stash_pop(&mut repo, stash_id);
-                return Err(proc_exit::bash::USAGE.with_message("Working tree is dirty, aborting"));
+    match stack.get(index) {
+        Some(location) => {
+            let snapshot = stack.load(&location).with_code(proc_exit::Code::FAILURE)?;
+            let mut plan = snapshot
+                .plan(&repo, &options)
+                .with_code(proc_exit::Code::FAILURE)?;
+            if rename {
+                plan = plan
+                    .renamed(
+                        &repo,
+                        args.prefix.as_deref().unwrap_or_default(),
+                        args.suffix.as_deref().unwrap_or_default(),
+                    )
+                    .with_code(proc_exit::Code::FAILURE)?;
             }
-
-            snapshot
-                .apply(&mut repo)
+            plan.check_worktrees(&repo)
                 .with_code(proc_exit::Code::FAILURE)?;
+            let worktree = plan.worktree;
+            if args.dry_run {
+                show_plan(&plan)?;
+                if let Some(worktree) = worktree {
+                    let palette = Palette::colored();
+                    writeln!(
+                        stdout(),
+                        "{:#}",
+                        Styled::new(
+                            format_args!("restore uncommitted changes from {worktree}"),
+                            palette.warn
+                        )
+                    )
+                    .with_code(proc_exit::Code::FAILURE)?;
+                }
+                return Ok(());
+            }
+
+            // Popping over the user's own changes could conflict with them
+            if worktree.is_some() && is_dirty(&repo) {
+                return Err(proc_exit::bash::USAGE.with_message(
+                    "Working tree is dirty, commit or stash it before restoring uncommitted changes",
+                ));
+            }
+
+            let applied = format!("{}@{{{}}}", stack.name, index);
+            restore(&mut repo, &plan, Some((&repo_config, &applied)))?;
+            if let Some(worktree) = worktree {
+                // Keep the snapshot when this fails, the branches alone are not all of it
+                restore_worktree(&mut repo, worktree)?;
+            }
 
-            stash_pop(&mut repo, stash_id);
             if pop {
-                let _ = std::fs::remove_file(&last);
+                if args.drop || (plan.skipped.is_empty() && !plan.partial) {
+                    stack.remove(index);
+                } else if plan.partial {
+                    log::info!("Keeping {applied}, only some branches were restored (use --drop)");
+                } else {
+                    log::warn!(
+                        "Keeping {applied}, protected branches were not restored (use --force)"
+                    );
+                }
             }
         }
         None => {
@@ -213,6 +642,212 @@ fn apply(args: args::ApplyArgs, pop: bool) -> proc_exit::ExitResult {
     Ok(())
 }
 
+fn undo(args: args::UndoArgs) -> proc_exit::ExitResult {
+    let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
+    let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
+    let mut repo = git_branch_stash::GitRepo::new(repo);
+    let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+        .with_code(proc_exit::Code::FAILURE)?;
+    set_remotes(&mut repo, &repo_config);
+    let mut stack = open_stack(git_branch_stash::Stack::UNDO_STACK, &repo, &repo_config);
+
+    // Branches created by the apply being undone should go away
+    let options = git_branch_stash::ApplyOptions {
+        prune: true,
+        protected_branches: repo_config.protected_branches().to_vec(),
+        force: args.force,
+        // Going back to before the apply, whatever it brought in is still in that snapshot
+        allow_divergent: true,
+        remotes: true,
+        tags: true,
+        head: true,
+        branch_config: true,
+        ..Default::default()
+    };
+
+    match stack.get(0) {
+        Some(location) => {
+            let snapshot = stack.load(&location).with_code(proc_exit::Code::FAILURE)?;
+            let plan = snapshot
+                .plan(&repo, &options)
+                .with_code(proc_exit::Code::FAILURE)?;
+            plan.check_worktrees(&repo)
+                .with_code(proc_exit::Code::FAILURE)?;
+            if args.dry_run {
+                return show_plan(&plan);
+            }
+
+            restore(&mut repo, &plan, None)?;
+
+            stack.remove(0);
+        }
+        None => {
+            log::warn!("Nothing to undo");
+        }
+    }
+
+    Ok(())
+}
+
+/// Apply `plan`, first saving the current branches to the undo stack when `undo` is set
+fn restore(
+    repo: &mut git_branch_stash::GitRepo,
+    plan: &git_branch_stash::Plan,
+    undo: Option<(&git_branch_stash::config::RepoConfig, &str)>,
+) -> proc_exit::ExitResult {
+    let stash_id = stash_push(repo, "branch-stash");
+    if is_dirty(repo) {
+        stash_pop(repo, stash_id);
+        return Err(proc_exit::bash::USAGE.with_message("Working tree is dirty, aborting"));
+    }
+
+    let result = match undo {
+        Some((repo_config, applied)) if !plan.is_empty() => save_undo(repo, repo_config, applied),
+        _ => Ok(None),
+    }
+    .and_then(|undo_stack| {
+        plan.apply(repo)
+            .with_code(proc_exit::Code::FAILURE)
+            .inspect_err(|_| {
+                // Nothing changed, so there is nothing to undo
+                if let Some(mut undo_stack) = undo_stack {
+                    undo_stack.pop();
+                }
+            })
+    });
+    stash_pop(repo, stash_id);
+    result
+}
+
+fn save_undo(
+    repo: &git_branch_stash::GitRepo,
+    repo_config: &git_branch_stash::config::RepoConfig,
+    applied: &str,
+) -> Result<Option<git_branch_stash::Stack>, proc_exit::Exit> {
+    let mut stack = open_stack(git_branch_stash::Stack::UNDO_STACK, repo, repo_config);
+    let previous = stack.peek();
+    let options = git_branch_stash::SnapshotOptions {
+        tags: true,
+        ..Default::default()
+    };
+    let mut snapshot = git_branch_stash::Snapshot::from_repo_with(repo, &options)
+        .with_code(proc_exit::Code::FAILURE)?;
+    snapshot.insert_message(&format!("Before applying {applied}"));
+    snapshot.metadata.insert(
+        "applied".to_owned(),
+        serde_json::Value::String(applied.to_owned()),
+    );
+    let location = stack.push(snapshot).with_code(proc_exit::Code::FAILURE)?;
+    if previous.as_ref() == Some(&location) {
+        // Reusing an existing entry, leave it be
+        return Ok(None);
+    }
+    log::debug!("Saved branches for `undo` as {location}");
+    Ok(Some(stack))
+}
+
+fn show_plan(plan: &git_branch_stash::Plan) -> proc_exit::ExitResult {
+    let palette = Palette::colored();
+
+    let mut stdout = stdout().lock();
+    if plan.is_empty() && plan.skipped.is_empty() {
+        writeln!(
+            stdout,
+            "{:#}",
+            Styled::new("Branches already match the snapshot", palette.good)
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
+    }
+    for change in plan.skipped.iter() {
+        let old_id = change.old_id.unwrap_or(git2::Oid::ZERO_SHA1);
+        let new_id = change.new_id.unwrap_or(git2::Oid::ZERO_SHA1);
+        writeln!(
+            stdout,
+            "{:#}",
+            Styled::new(
+                format_args!(
+                    "skip {}: {old_id} -> {new_id} (protected, use --force)",
+                    change.name
+                ),
+                palette.hint
+            )
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
+    }
+    for change in plan.changes.iter() {
+        let (action, style) = match change.kind() {
+            git_branch_stash::ChangeKind::Create => ("create", palette.good),
+            git_branch_stash::ChangeKind::Update => ("update", palette.warn),
+            git_branch_stash::ChangeKind::Delete => ("delete", palette.error),
+        };
+        let old_id = change.old_id.unwrap_or(git2::Oid::ZERO_SHA1);
+        let new_id = change.new_id.unwrap_or(git2::Oid::ZERO_SHA1);
+        let name = match change.ref_kind {
+            git_branch_stash::RefKind::Branch => change.name.clone(),
+            _ => change.ref_name(),
+        };
+        write!(
+            stdout,
+            "{:#}",
+            Styled::new(format_args!("{action} {name}: {old_id} -> {new_id}"), style)
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
+        match change.movement {
+            Some(git_branch_stash::Movement::FastForward) => {
+                write!(stdout, " {:#}", Styled::new("(fast-forward)", palette.hint))
+            }
+            Some(git_branch_stash::Movement::Rewind) => {
+                write!(stdout, " {:#}", Styled::new("(rewind)", palette.hint))
+            }
+            Some(git_branch_stash::Movement::Divergent) => write!(
+                stdout,
+                " {:#}",
+                Styled::new("(divergent, commits will be lost)", palette.error)
+            ),
+            None => Ok(()),
+        }
+        .with_code(proc_exit::Code::FAILURE)?;
+        if change.head {
+            write!(
+                stdout,
+                " {:#}",
+                Styled::new("(HEAD, working tree will be checked out)", palette.hint)
+            )
+            .with_code(proc_exit::Code::FAILURE)?;
+        }
+        writeln!(stdout).with_code(proc_exit::Code::FAILURE)?;
+    }
+    match plan.head.as_ref() {
+        Some(git_branch_stash::Head::Branch(name)) => writeln!(
+            stdout,
+            "{:#}",
+            Styled::new(format_args!("switch to {name}"), palette.warn)
+        ),
+        Some(git_branch_stash::Head::Detached(id)) => writeln!(
+            stdout,
+            "{:#}",
+            Styled::new(format_args!("detach HEAD at {id}"), palette.warn)
+        ),
+        None => Ok(()),
+    }
+    .with_code(proc_exit::Code::FAILURE)?;
+    for (name, values) in plan.config.iter() {
+        for (var, value) in values.iter() {
+            writeln!(
+                stdout,
+                "{:#}",
+                Styled::new(
+                    format_args!("config branch.{name}.{var}={value}"),
+                    palette.good
+                )
+            )
+            .with_code(proc_exit::Code::FAILURE)?;
+        }
+    }
+
+    Ok(())
+}
+
 fn stacks(_args: args::StacksArgs) -> proc_exit::ExitResult {
     let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
     let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
@@ -226,6 +861,170 @@ fn stacks(_args: args::StacksArgs) -> proc_exit::ExitResult {
     Ok(())
 }
 
+fn config(args: args::ConfigArgs) -> proc_exit::ExitResult {
+    let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
+    let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
+
+    match args.action.unwrap_or(args::ConfigAction::Show) {
+        args::ConfigAction::Show => {
+            let repo_config = git_branch_stash::config::RepoConfig::from_all(&repo)
+                .with_code(proc_exit::Code::FAILURE)?;
+            write!(stdout(), "{repo_config}").with_code(proc_exit::Code::FAILURE)?;
+        }
+        args::ConfigAction::Protected => {
+            let repo = git_branch_stash::GitRepo::new(repo);
+            let repo_config = git_branch_stash::config::RepoConfig::from_all(repo.raw())
+                .with_code(proc_exit::Code::FAILURE)?;
+            let protected = git_branch_stash::BranchPatterns::new(
+                repo_config.protected_branches().iter().map(|p| p.as_str()),
+            )
+            .with_code(proc_exit::Code::FAILURE)?;
+
+            let snapshot =
+                git_branch_stash::Snapshot::from_repo(&repo).with_code(proc_exit::Code::FAILURE)?;
+            let mut stdout = stdout().lock();
+            for branch in snapshot.branches.iter() {
+                if protected.is_match(&branch.name) {
+                    writeln!(stdout, "{}", branch.name).with_code(proc_exit::Code::FAILURE)?;
+                }
+            }
+        }
+        args::ConfigAction::Protect(sub_args) => {
+            let mut repo_config = load_config_target(&repo, &sub_args.target)?;
+            let protected_branches = repo_config.protected_branches.get_or_insert_with(Vec::new);
+            if protected_branches.contains(&sub_args.glob) {
+                log::info!("{} is already protected", sub_args.glob);
+                return Ok(());
+            }
+            protected_branches.push(sub_args.glob);
+            write_config_target(&repo, &repo_config, &sub_args.target)?;
+        }
+        args::ConfigAction::Unprotect(sub_args) => {
+            let mut repo_config = load_config_target(&repo, &sub_args.target)?;
+            let protected_branches = repo_config.protected_branches.get_or_insert_with(Vec::new);
+            let len = protected_branches.len();
+            protected_branches.retain(|p| *p != sub_args.glob);
+            if protected_branches.len() == len {
+                return Err(proc_exit::bash::USAGE.with_message(format!(
+                    "{} is not protected in {}",
+                    sub_args.glob,
+                    if sub_args.target.shared {
+                        "$REPO/.gitconfig"
+                    } else {
+                        "$REPO/.git/config"
+                    }
+                )));
+            }
+            write_config_target(&repo, &repo_config, &sub_args.target)?;
+        }
+        args::ConfigAction::Capacity(sub_args) => {
+            let mut repo_config = load_config_target(&repo, &sub_args.target)?;
+            repo_config.capacity = Some(sub_args.capacity);
+            write_config_target(&repo, &repo_config, &sub_args.target)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn load_config_target(
+    repo: &git2::Repository,
+    target: &args::ConfigTarget,
+) -> Result<git_branch_stash::config::RepoConfig, proc_exit::Exit> {
+    let repo_config = if target.shared {
+        git_branch_stash::config::RepoConfig::from_workdir(repo)
+    } else {
+        git_branch_stash::config::RepoConfig::from_repo(repo)
+    };
+    repo_config.with_code(proc_exit::Code::FAILURE)
+}
+
+fn write_config_target(
+    repo: &git2::Repository,
+    repo_config: &git_branch_stash::config::RepoConfig,
+    target: &args::ConfigTarget,
+) -> proc_exit::ExitResult {
+    let result = if target.shared {
+        repo_config.write_workdir(repo)
+    } else {
+        repo_config.write_repo(repo)
+    };
+    result.with_code(proc_exit::Code::FAILURE)
+}
+
+fn verify(_args: args::VerifyArgs) -> proc_exit::ExitResult {
+    let palette = Palette::colored();
+
+    let cwd = std::env::current_dir().with_code(proc_exit::bash::USAGE)?;
+    let repo = git2::Repository::discover(cwd).with_code(proc_exit::bash::USAGE)?;
+    let repo = git_branch_stash::GitRepo::new(repo);
+
+    let mut broken = 0;
+    let mut stdout = stdout().lock();
+    for stack in git_branch_stash::Stack::all(&repo) {
+        let snapshots: Vec<_> = stack.iter().collect();
+        for (i, location) in snapshots.iter().enumerate() {
+            let index = snapshots.len() - 1 - i;
+            let snapshot = match stack.load(location) {
+                Ok(snapshot) => snapshot,
+                Err(err) => {
+                    broken += 1;
+                    writeln!(
+                        stdout,
+                        "{:#}",
+                        Styled::new(
+                            format_args!("{}@{{{index}}}: failed to load: {err}", stack.name),
+                            palette.error
+                        )
+                    )
+                    .with_code(proc_exit::Code::FAILURE)?;
+                    continue;
+                }
+            };
+            let missing = snapshot.missing(&repo);
+            let missing_worktree = snapshot.missing_worktree(&repo);
+            if !missing.is_empty() || missing_worktree.is_some() {
+                broken += 1;
+            }
+            if let Some(id) = missing_worktree {
+                writeln!(
+                    stdout,
+                    "{:#}",
+                    Styled::new(
+                        format_args!(
+                            "{}@{{{index}}}: uncommitted changes are missing {id}",
+                            stack.name
+                        ),
+                        palette.error
+                    )
+                )
+                .with_code(proc_exit::Code::FAILURE)?;
+            }
+            for branch in missing {
+                writeln!(
+                    stdout,
+                    "{:#}",
+                    Styled::new(
+                        format_args!(
+                            "{}@{{{index}}}: {} is missing {}",
+                            stack.name, branch.name, branch.id
+                        ),
+                        palette.error
+                    )
+                )
+                .with_code(proc_exit::Code::FAILURE)?;
+            }
+        }
+    }
+
+    if broken == 0 {
+        Ok(())
+    } else {
+        Err(proc_exit::Code::FAILURE
+            .with_message(format!("{broken} snapshot(s) cannot be fully restored")))
+    }
+}
+
 fn is_dirty(repo: &git_branch_stash::GitRepo) -> bool {
     if repo.raw().state() != git2::RepositoryState::Clean {
         log::trace!("Repository status is unclean: {:?}", repo.raw().state());
@@ -281,6 +1080,46 @@ fn stash_push(repo: &mut git_branch_stash::GitRepo, context: &str) -> Option<git
     }
 }
 
+/// Put a captured stash commit back on the stash list and pop it over the working tree
+///
+/// On failure, like a conflict, it is left on the stash list for the user to resolve.
+fn restore_worktree(
+    repo: &mut git_branch_stash::GitRepo,
+    stash_id: git2::Oid,
+) -> proc_exit::ExitResult {
+    repo.raw_mut()
+        .reference_ensure_log("refs/stash")
+        .with_code(proc_exit::Code::FAILURE)?;
+    repo.raw_mut()
+        .reference(
+            "refs/stash",
+            stash_id,
+            true,
+            "branch-stash: restore uncommitted changes",
+        )
+        .with_code(proc_exit::Code::FAILURE)?;
+    // Just made it the most recent entry, and like `git stash pop`, only drop it when it applies
+    // cleanly
+    let applied = repo.raw_mut().stash_apply(0, None).and_then(|()| {
+        let conflicted = repo.raw().index()?.has_conflicts();
+        if conflicted {
+            Err(git2::Error::from_str("conflicts with the working tree"))
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(err) = applied {
+        return Err(proc_exit::Code::FAILURE.with_message(format!(
+            "Failed to restore uncommitted changes, left as stash@{{0}} ({stash_id}): {err}"
+        )));
+    }
+    repo.raw_mut()
+        .stash_drop(0)
+        .with_code(proc_exit::Code::FAILURE)?;
+    log::info!("Restored uncommitted changes from {stash_id}");
+    Ok(())
+}
+
 fn stash_pop(repo: &mut git_branch_stash::GitRepo, stash_id: Option<git2::Oid>) {
     if let Some(stash_id) = stash_id {
         let mut index = None;